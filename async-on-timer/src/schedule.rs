@@ -0,0 +1,308 @@
+use futures::future::{self, Either, FutureExt};
+use futures::stream::{FuturesUnordered, Stream, StreamExt};
+use std::future::Future;
+use std::str::FromStr;
+
+///
+/// What to do with a tick that arrives while the previous run hasn't finished yet.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overrun {
+    /// Drop the tick.
+    Skip,
+    /// Remember the tick and run once for each of them after the current run finished.
+    Queue,
+    /// Start another run right away.
+    Concurrent,
+}
+
+impl FromStr for Overrun {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(Overrun::Skip),
+            "queue" => Ok(Overrun::Queue),
+            "concurrent" => Ok(Overrun::Concurrent),
+            _ => Err(format!(
+                "unknown overrun policy '{}', expected skip, queue or concurrent",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// Call `job` on every tick of `ticks`, handling ticks during a run according to `overrun`.
+/// The clock is whatever drives `ticks`, e.g. `async_std::stream::interval()`.
+///
+/// Returns when `ticks` ends and all started runs have finished. Queued runs are dropped.
+///
+pub async fn run_on_ticks<S, F, Fut>(ticks: S, overrun: Overrun, mut job: F)
+where
+    S: Stream<Item = ()> + Unpin,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut ticks = ticks.fuse();
+    let mut running = FuturesUnordered::new();
+    let mut queued = 0usize;
+
+    loop {
+        // finished runs first, so a tick never sees a run that is done as still running
+        futures::select_biased! {
+            _ = running.select_next_some() => {
+                if queued > 0 && running.is_empty() {
+                    queued -= 1;
+                    running.push(job());
+                }
+            }
+            tick = ticks.next() => match tick {
+                Some(()) if running.is_empty() || overrun == Overrun::Concurrent => {
+                    running.push(job())
+                }
+                Some(()) if overrun == Overrun::Queue => queued += 1,
+                Some(()) => {}
+                None => break,
+            },
+        }
+    }
+    while running.next().await.is_some() {}
+}
+
+///
+/// How [`run_until_shutdown`] ended.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shutdown {
+    /// The ticks ended and all runs finished.
+    Completed,
+    /// Shut down after all in-flight runs finished.
+    Drained,
+    /// Shut down after cancelling the runs that didn't finish in time.
+    Cancelled,
+}
+
+///
+/// Like [`run_on_ticks`], but stops starting new runs when `shutdown` resolves. In-flight
+/// runs may then finish until the future returned by `drain_timeout` resolves, after
+/// which they are cancelled (dropped).
+///
+pub async fn run_until_shutdown<S, F, Fut, Sd, T, D>(
+    ticks: S,
+    overrun: Overrun,
+    job: F,
+    shutdown: Sd,
+    drain_timeout: T,
+) -> Shutdown
+where
+    S: Stream<Item = ()> + Unpin,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+    Sd: Future<Output = ()>,
+    T: FnOnce() -> D,
+    D: Future<Output = ()>,
+{
+    let shutdown = shutdown.shared();
+    let scheduler = run_on_ticks(ticks.take_until(shutdown.clone()), overrun, job);
+    futures::pin_mut!(scheduler);
+
+    match future::select(scheduler, shutdown.clone()).await {
+        // the ticks may have ended because of the shutdown
+        Either::Left(_) if shutdown.peek().is_some() => Shutdown::Drained,
+        Either::Left(_) => Shutdown::Completed,
+        Either::Right((_, scheduler)) => {
+            let timeout = drain_timeout();
+            futures::pin_mut!(timeout);
+            match future::select(scheduler, timeout).await {
+                Either::Left(_) => Shutdown::Drained,
+                Either::Right(_) => Shutdown::Cancelled,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::{mpsc, oneshot};
+    use futures::executor::LocalPool;
+    use futures::task::LocalSpawnExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    ///
+    /// A test clock: ticks, finishing runs, the shutdown and the drain timeout are all
+    /// triggered by hand.
+    ///
+    struct TestClock {
+        pool: LocalPool,
+        ticks: mpsc::UnboundedSender<()>,
+        runs: Rc<RefCell<Vec<Option<oneshot::Sender<()>>>>>,
+        shutdown: Option<oneshot::Sender<()>>,
+        drain_timeout: Option<oneshot::Sender<()>>,
+        outcome: Rc<RefCell<Option<Shutdown>>>,
+    }
+
+    impl TestClock {
+        fn start(overrun: Overrun) -> Self {
+            let pool = LocalPool::new();
+            let (ticks, rx) = mpsc::unbounded();
+            let runs = Rc::new(RefCell::new(vec![]));
+            let job_runs = runs.clone();
+            let (shutdown, shutdown_rx) = oneshot::channel();
+            let (drain_timeout, drain_timeout_rx) = oneshot::channel();
+            let outcome = Rc::new(RefCell::new(None));
+            let scheduler_outcome = outcome.clone();
+            pool.spawner()
+                .spawn_local(async move {
+                    let result = run_until_shutdown(
+                        rx,
+                        overrun,
+                        move || {
+                            let (done, wait) = oneshot::channel();
+                            job_runs.borrow_mut().push(Some(done));
+                            async move {
+                                let _ = wait.await;
+                            }
+                        },
+                        shutdown_rx.map(|_| ()),
+                        || drain_timeout_rx.map(|_| ()),
+                    )
+                    .await;
+                    *scheduler_outcome.borrow_mut() = Some(result);
+                })
+                .unwrap();
+            TestClock {
+                pool,
+                ticks,
+                runs,
+                shutdown: Some(shutdown),
+                drain_timeout: Some(drain_timeout),
+                outcome,
+            }
+        }
+
+        fn tick(&mut self) {
+            self.ticks.unbounded_send(()).unwrap();
+            self.pool.run_until_stalled();
+        }
+
+        fn finish(&mut self, run: usize) {
+            let done = self.runs.borrow_mut()[run].take().unwrap();
+            done.send(()).unwrap();
+            self.pool.run_until_stalled();
+        }
+
+        fn shutdown(&mut self) {
+            self.shutdown.take().unwrap().send(()).unwrap();
+            self.pool.run_until_stalled();
+        }
+
+        fn drain_timeout(&mut self) {
+            self.drain_timeout.take().unwrap().send(()).unwrap();
+            self.pool.run_until_stalled();
+        }
+
+        fn started(&self) -> usize {
+            self.runs.borrow().len()
+        }
+
+        fn running(&self) -> usize {
+            self.runs.borrow().iter().filter(|r| r.is_some()).count()
+        }
+
+        fn outcome(&self) -> Option<Shutdown> {
+            *self.outcome.borrow()
+        }
+    }
+
+    #[test]
+    fn test_overrun_skip() {
+        let mut clock = TestClock::start(Overrun::Skip);
+        clock.tick();
+        clock.tick();
+        clock.tick();
+        assert_eq!(clock.started(), 1);
+        clock.finish(0);
+        assert_eq!(clock.running(), 0);
+        clock.tick();
+        assert_eq!(clock.started(), 2);
+    }
+
+    #[test]
+    fn test_overrun_queue() {
+        let mut clock = TestClock::start(Overrun::Queue);
+        clock.tick();
+        clock.tick();
+        clock.tick();
+        assert_eq!(clock.started(), 1);
+        clock.finish(0);
+        assert_eq!((clock.started(), clock.running()), (2, 1));
+        clock.finish(1);
+        assert_eq!((clock.started(), clock.running()), (3, 1));
+        clock.finish(2);
+        assert_eq!((clock.started(), clock.running()), (3, 0));
+    }
+
+    #[test]
+    fn test_overrun_concurrent() {
+        let mut clock = TestClock::start(Overrun::Concurrent);
+        clock.tick();
+        clock.tick();
+        assert_eq!((clock.started(), clock.running()), (2, 2));
+        clock.finish(1);
+        clock.tick();
+        assert_eq!((clock.started(), clock.running()), (3, 2));
+    }
+
+    #[test]
+    fn test_shutdown_drained() {
+        let mut clock = TestClock::start(Overrun::Queue);
+        clock.tick();
+        clock.tick();
+        clock.shutdown();
+        assert_eq!(clock.outcome(), None);
+        // neither new nor queued runs start after the shutdown
+        clock.tick();
+        clock.finish(0);
+        assert_eq!(clock.started(), 1);
+        assert_eq!(clock.outcome(), Some(Shutdown::Drained));
+    }
+
+    #[test]
+    fn test_shutdown_cancelled() {
+        let mut clock = TestClock::start(Overrun::Concurrent);
+        clock.tick();
+        clock.tick();
+        clock.shutdown();
+        clock.finish(0);
+        assert_eq!(clock.outcome(), None);
+        clock.drain_timeout();
+        assert_eq!(clock.outcome(), Some(Shutdown::Cancelled));
+    }
+
+    #[test]
+    fn test_shutdown_idle() {
+        let mut clock = TestClock::start(Overrun::Skip);
+        clock.shutdown();
+        assert_eq!(clock.outcome(), Some(Shutdown::Drained));
+    }
+
+    #[test]
+    fn test_run_until_shutdown_ends_with_ticks() {
+        let runs = RefCell::new(0);
+        let outcome = futures::executor::block_on(run_until_shutdown(
+            futures::stream::iter(vec![(), (), ()]),
+            Overrun::Queue,
+            || {
+                *runs.borrow_mut() += 1;
+                async {}
+            },
+            future::pending(),
+            future::pending,
+        ));
+        assert_eq!(outcome, Shutdown::Completed);
+        assert_eq!(*runs.borrow(), 3);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::bar::Bar;
+use crate::series::TimeSeries;
+use chrono::prelude::*;
+use std::fmt;
+
+///
+/// A dividend or split of a symbol, the reasons for the difference between the
+/// raw and the adjusted closing price.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorporateAction {
+    /// The ex-date.
+    pub timestamp: DateTime<Utc>,
+    pub kind: ActionKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActionKind {
+    /// A cash dividend per share.
+    Dividend { amount: f64 },
+    /// `denominator` shares became `numerator` shares, e.g. 4:1.
+    Split { numerator: f64, denominator: f64 },
+}
+
+impl fmt::Display for ActionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionKind::Dividend { amount } => write!(f, "dividend,{}", amount),
+            ActionKind::Split {
+                numerator,
+                denominator,
+            } => write!(f, "split,{}:{}", numerator, denominator),
+        }
+    }
+}
+
+impl fmt::Display for CorporateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.timestamp.to_rfc3339(), self.kind)
+    }
+}
+
+///
+/// The value of holding one share bought at the first raw close of `bars` with all
+/// dividends reinvested. Splits multiply the number of shares, so they don't change the value.
+/// An action applies to the first bar at or after its ex-date; actions before the second
+/// bar are ignored since there is no previous close to compare with.
+///
+/// # Returns
+///
+/// A series with one value per bar, starting with the first close.
+///
+pub fn total_return(bars: &[Bar], actions: &[CorporateAction]) -> TimeSeries {
+    let mut value = match bars.first() {
+        Some(first) => first.close,
+        None => return TimeSeries::default(),
+    };
+    let mut series = vec![(bars[0].timestamp, value)];
+    for pair in bars.windows(2) {
+        let (previous, bar) = (&pair[0], &pair[1]);
+        let (mut shares, mut dividends) = (1.0, 0.0);
+        for action in actions
+            .iter()
+            .filter(|a| previous.timestamp < a.timestamp && a.timestamp <= bar.timestamp)
+        {
+            match action.kind {
+                // paid on the shares held before any split of the same bar
+                ActionKind::Dividend { amount } => dividends += amount,
+                ActionKind::Split {
+                    numerator,
+                    denominator,
+                } => shares *= numerator / denominator,
+            }
+        }
+        if previous.close != 0.0 {
+            value = value * (bar.close * shares + dividends) / previous.close;
+        }
+        series.push((bar.timestamp, value));
+    }
+    series.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::series::tests::day;
+
+    fn bar(i: usize, close: f64) -> Bar {
+        Bar {
+            timestamp: day(i),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            adjclose: close,
+            volume: 0,
+        }
+    }
+
+    #[test]
+    fn test_total_return() {
+        let bars = vec![
+            bar(0, 100.0),
+            bar(1, 110.0),
+            bar(2, 27.0),
+            bar(3, 27.0),
+            bar(4, 30.0),
+        ];
+        let actions = vec![
+            CorporateAction {
+                timestamp: day(2),
+                kind: ActionKind::Split {
+                    numerator: 4.0,
+                    denominator: 1.0,
+                },
+            },
+            CorporateAction {
+                timestamp: day(3),
+                kind: ActionKind::Dividend { amount: 0.27 },
+            },
+        ];
+
+        let series = total_return(&bars, &actions);
+        let expected = [100.0, 110.0, 108.0, 109.08, 121.2];
+        assert_eq!(
+            series.timestamps(),
+            &[day(0), day(1), day(2), day(3), day(4)]
+        );
+        for (value, expected) in series.values().iter().zip(&expected) {
+            assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+        }
+        assert_eq!(total_return(&bars[..2], &[]).values(), &[100.0, 110.0]);
+        assert!(total_return(&[], &actions).is_empty());
+    }
+
+    #[test]
+    fn test_display() {
+        let dividend = CorporateAction {
+            timestamp: day(0),
+            kind: ActionKind::Dividend { amount: 0.205 },
+        };
+        assert_eq!(
+            dividend.to_string(),
+            "2021-01-04T00:00:00+00:00,dividend,0.205"
+        );
+        let split = ActionKind::Split {
+            numerator: 4.0,
+            denominator: 1.0,
+        };
+        assert_eq!(split.to_string(), "split,4:1");
+    }
+}
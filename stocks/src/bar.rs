@@ -0,0 +1,102 @@
+use crate::provider::Quote;
+use crate::series::TimeSeries;
+use chrono::prelude::*;
+use std::str::FromStr;
+
+///
+/// The prices and volume of a symbol over one period (e.g. a trading day).
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bar {
+    /// The start of the period.
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// The closing price adjusted for dividends and splits.
+    pub adjclose: f64,
+    pub volume: u64,
+}
+
+///
+/// Which closing price signals are calculated on.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PriceBasis {
+    /// Adjusted for dividends and splits (`adjclose`).
+    #[default]
+    Adjusted,
+    /// As traded (`close`).
+    Raw,
+}
+
+impl FromStr for PriceBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "adjusted" => Ok(PriceBasis::Adjusted),
+            "raw" => Ok(PriceBasis::Raw),
+            _ => Err(format!(
+                "unknown price basis '{}', expected adjusted or raw",
+                s
+            )),
+        }
+    }
+}
+
+impl Bar {
+    pub fn closing_price(&self, basis: PriceBasis) -> f64 {
+        match basis {
+            PriceBasis::Adjusted => self.adjclose,
+            PriceBasis::Raw => self.close,
+        }
+    }
+
+    ///
+    /// The closing prices of `bars`, the column all close-based signals work on.
+    ///
+    pub fn closes(bars: &[Bar], basis: PriceBasis) -> TimeSeries {
+        bars.iter()
+            .map(|bar| (bar.timestamp, bar.closing_price(basis)))
+            .collect()
+    }
+}
+
+impl From<&Quote> for Bar {
+    fn from(quote: &Quote) -> Self {
+        Bar {
+            timestamp: Utc.timestamp(quote.timestamp as i64, 0),
+            open: quote.open,
+            high: quote.high,
+            low: quote.low,
+            close: quote.close,
+            adjclose: quote.adjclose,
+            volume: quote.volume,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::tests::quote;
+
+    #[test]
+    fn test_bar_from_quote() {
+        let quote = Quote {
+            volume: 1200,
+            close: 3.5,
+            ..quote(86400, 3.0)
+        };
+        let bars = [Bar::from(&quote)];
+        assert_eq!(bars[0].timestamp, Utc.ymd(1970, 1, 2).and_hms(0, 0, 0));
+        assert_eq!(
+            (bars[0].close, bars[0].adjclose, bars[0].volume),
+            (3.5, 3.0, 1200)
+        );
+        assert_eq!(Bar::closes(&bars, PriceBasis::Adjusted).values(), &[3.0]);
+        assert_eq!(Bar::closes(&bars, PriceBasis::Raw).values(), &[3.5]);
+    }
+}
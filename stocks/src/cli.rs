@@ -0,0 +1,276 @@
+use crate::bar::PriceBasis;
+use crate::error::FetchErrorKind;
+use crate::interval::Interval;
+use crate::provider::{
+    CachedProvider, CassettePlayer, CassetteRecorder, CsvProvider, QuoteProvider, YahooProvider,
+    YCHART_URL,
+};
+use crate::report::ReportOptions;
+use crate::retry::RetryPolicy;
+use crate::signals::BollingerBands;
+use chrono::prelude::*;
+use clap::Clap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+//
+// Command line options shared by all binaries. Use `#[clap(flatten)]` to include them.
+// (A doc comment here would replace the binary's `about` text.)
+//
+#[derive(Clap)]
+pub struct Opts {
+    #[clap(short, long, default_value = "AAPL,MSFT,UBER,GOOG")]
+    pub symbols: String,
+    #[clap(short, long)]
+    pub from: String,
+    /// Read quotes from <csv-dir>/<SYMBOL>.csv instead of yahoo! finance
+    #[clap(long)]
+    pub csv_dir: Option<PathBuf>,
+    /// Base URL of the yahoo! finance chart API, e.g. a local mock server
+    #[clap(long, default_value = YCHART_URL)]
+    pub yahoo_url: String,
+    /// Directory for cached yahoo! finance quotes [default: $XDG_CACHE_HOME/stocks or ~/.cache/stocks]
+    #[clap(long)]
+    pub cache_dir: Option<PathBuf>,
+    /// Always download all quotes, without reading or writing the cache
+    #[clap(long)]
+    pub no_cache: bool,
+    /// Remove all cached quotes before fetching
+    #[clap(long, conflicts_with_all = &["csv-dir", "replay"])]
+    pub purge_cache: bool,
+    /// Write all responses of the provider to <record>, to reproduce the run with --replay
+    #[clap(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Replay the responses recorded with --record instead of fetching quotes
+    #[clap(long)]
+    pub replay: Option<PathBuf>,
+    /// Maximum number of symbols fetched at the same time
+    #[clap(long, default_value = "8")]
+    pub max_concurrent: usize,
+    /// Output the symbols sorted instead of in the given order
+    #[clap(long)]
+    pub sorted: bool,
+    /// Attempts per symbol before giving up, including the first one
+    #[clap(long, default_value = "3")]
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further one
+    #[clap(long, default_value = "500ms", parse(try_from_str = parse_duration))]
+    pub retry_base_delay: Duration,
+    /// Maximum delay between two attempts
+    #[clap(long, default_value = "30s", parse(try_from_str = parse_duration))]
+    pub retry_max_delay: Duration,
+    /// Fraction (0.0 to 1.0) of each retry delay that is randomized
+    #[clap(long, default_value = "0.5")]
+    pub retry_jitter: f64,
+    /// Comma separated kinds of errors that are retried: transport, rate-limited, http (5xx), rejected (other statuses), unknown-symbol, malformed-response, empty-data, invalid-range, io
+    #[clap(long, default_value = "transport,rate-limited,http", validator = validate_error_kinds)]
+    pub retry_on: String,
+    /// Closing prices the signals are calculated on: adjusted (for dividends and splits) or raw
+    #[clap(long, default_value = "adjusted")]
+    pub price_basis: PriceBasis,
+    /// Period of a bar: 1m, 5m, 15m, 1h, 1d or 1wk. Moving averages count bars of this period
+    #[clap(long, default_value = "1d")]
+    pub bar_interval: Interval,
+    /// Add a column with the annualized volatility of the log returns over this many bars
+    #[clap(long)]
+    pub volatility_window: Option<usize>,
+    /// Add a column with the exponential moving average over this many bars
+    #[clap(long)]
+    pub ema_span: Option<usize>,
+    /// Add a column with the relative strength index over this many bars, e.g. 14
+    #[clap(long)]
+    pub rsi_period: Option<usize>,
+    /// Add a column with the distance of the price from the upper Bollinger band, as window,k bars and standard deviations, e.g. 20,2
+    #[clap(long)]
+    pub bollinger: Option<BollingerBands>,
+    /// Bars per year to annualize with [default: 252 for 1d, 52 for 1wk, 252 days of 6.5 hours for intraday]
+    #[clap(long)]
+    pub periods_per_year: Option<f64>,
+    /// Add beta, alpha, correlation and excess return of each symbol against this one, e.g. SPY
+    #[clap(long)]
+    pub benchmark: Option<String>,
+    /// Annual risk-free rate for the alpha against --benchmark and Sharpe and Sortino ratios, e.g. 0.02 for 2%
+    #[clap(long, default_value = "0.0")]
+    pub risk_free_rate: f64,
+}
+
+impl Opts {
+    pub fn symbols(&self) -> Vec<&str> {
+        self.symbols.split(',').collect()
+    }
+
+    pub fn from(&self) -> DateTime<Utc> {
+        self.from.parse().expect("Couldn't parse 'from' date")
+    }
+
+    pub fn report_options(&self) -> ReportOptions {
+        ReportOptions {
+            max_concurrent: self.max_concurrent,
+            sorted: self.sorted,
+            retry: RetryPolicy {
+                max_attempts: self.retries.max(1),
+                base_delay: self.retry_base_delay,
+                max_delay: self.retry_max_delay,
+                jitter: self.retry_jitter,
+                retryable: self.retry_on.split(',').map(str::to_string).collect(),
+            },
+            basis: self.price_basis,
+            interval: self.bar_interval,
+            volatility_window: self.volatility_window,
+            ema_span: self.ema_span,
+            rsi_period: self.rsi_period,
+            bollinger: self.bollinger,
+            periods_per_year: self.periods_per_year(),
+            benchmark: self.benchmark.clone(),
+            risk_free_rate: self.risk_free_rate,
+            // the options of modes only some binaries have are set by those
+            ..ReportOptions::default()
+        }
+    }
+
+    pub fn periods_per_year(&self) -> f64 {
+        self.periods_per_year
+            .unwrap_or_else(|| self.bar_interval.periods_per_year())
+    }
+
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir.clone().unwrap_or_else(|| {
+            std::env::var_os("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .unwrap_or_else(std::env::temp_dir)
+                .join("stocks")
+        })
+    }
+
+    ///
+    /// Check `from..=to` against the limits of the selected provider for `--bar-interval`.
+    ///
+    pub fn check_range(&self, from: &DateTime<Utc>, to: &DateTime<Utc>) -> Result<(), String> {
+        match (&self.csv_dir, &self.replay) {
+            (None, None) => YahooProvider::check_range(self.bar_interval, from, to),
+            _ => Ok(()),
+        }
+    }
+
+    ///
+    /// The start of a run from `from` to `to`: no earlier than the [`YahooProvider::max_range`]
+    /// of `--bar-interval` before `to` if quotes come from yahoo! finance, so that runs with a
+    /// later `to` (e.g. on a timer) keep a rolling window of the intraday bars it still serves.
+    ///
+    pub fn window_start(&self, from: &DateTime<Utc>, to: &DateTime<Utc>) -> DateTime<Utc> {
+        let max_range = match (&self.csv_dir, &self.replay) {
+            (None, None) => YahooProvider::max_range(self.bar_interval),
+            _ => None,
+        };
+        match max_range {
+            Some(max_range) => (*to - max_range).max(*from),
+            None => *from,
+        }
+    }
+
+    ///
+    /// The quote provider selected on the command line, recording or replaying its responses
+    /// if requested. Purges the cache first if requested.
+    ///
+    pub async fn provider(&self) -> std::io::Result<Box<dyn QuoteProvider>> {
+        if let Some(path) = &self.replay {
+            return Ok(Box::new(CassettePlayer::open(path).await?));
+        }
+        let provider = self.source().await?;
+        match &self.record {
+            Some(path) => Ok(Box::new(CassetteRecorder::create(provider, path).await?)),
+            None => Ok(provider),
+        }
+    }
+
+    async fn source(&self) -> std::io::Result<Box<dyn QuoteProvider>> {
+        if let Some(dir) = &self.csv_dir {
+            return Ok(Box::new(CsvProvider::new(dir)));
+        }
+        let cached =
+            CachedProvider::new(YahooProvider::with_url(&self.yahoo_url), self.cache_dir());
+        if self.purge_cache {
+            cached.purge().await?;
+        }
+        if self.no_cache {
+            Ok(Box::new(YahooProvider::with_url(&self.yahoo_url)))
+        } else {
+            Ok(Box::new(cached))
+        }
+    }
+}
+
+///
+/// Parse a duration like `500ms`, `30s`, `5m` or `1h`. A number without a unit is in seconds.
+///
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "" | "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 60 * 60)),
+        _ => Err(format!("unknown unit '{}' in duration '{}'", unit, s)),
+    }
+}
+
+fn validate_error_kinds(s: &str) -> Result<(), String> {
+    match s
+        .split(',')
+        .find(|name| !FetchErrorKind::NAMES.contains(name))
+    {
+        Some(name) => Err(format!("unknown error kind '{}'", name)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+
+    #[test]
+    fn test_window_start() {
+        let opts = |args: &[&str]| {
+            Opts::parse_from(
+                ["stocks", "--from", "2021-01-01T00:00:00Z"]
+                    .iter()
+                    .chain(args),
+            )
+        };
+        let from = Utc.ymd(2021, 1, 1).and_hms(0, 0, 0);
+        let soon = Utc.ymd(2021, 1, 5).and_hms(0, 0, 0);
+        let later = Utc.ymd(2021, 3, 1).and_hms(0, 0, 0);
+
+        let daily = opts(&[]);
+        assert_eq!(daily.window_start(&from, &later), from);
+        let intraday = opts(&["--bar-interval", "5m"]);
+        assert_eq!(intraday.window_start(&from, &soon), from);
+        let start = intraday.window_start(&from, &later);
+        assert_eq!(start, later - chrono::Duration::days(59));
+        let csv = opts(&["--bar-interval", "5m", "--csv-dir", "quotes"]);
+        assert_eq!(csv.window_start(&from, &later), from);
+    }
+
+    #[test]
+    fn test_validate_error_kinds() {
+        assert!(validate_error_kinds("transport,rate-limited").is_ok());
+        assert!(validate_error_kinds("transport,timeout").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::series::TimeSeries;
+use chrono::prelude::*;
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::str::FromStr;
+
+///
+/// Pairwise correlations and covariances of the returns of several symbols over the same bars.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorrelationMatrix {
+    /// The timestamp of the last return, `None` if there are no returns.
+    pub end: Option<DateTime<Utc>>,
+    /// The number of returns per symbol.
+    pub observations: usize,
+    pub symbols: Vec<String>,
+    /// `correlation[i][j]` of `symbols[i]` and `symbols[j]`, `None` if either has no variance.
+    pub correlation: Vec<Vec<Option<f64>>>,
+    /// The (sample) covariance, `None` if there are fewer than two returns or either has an
+    /// undefined (NaN) return.
+    pub covariance: Vec<Vec<Option<f64>>>,
+}
+
+impl CorrelationMatrix {
+    ///
+    /// The matrix of `returns`, which must have the same timestamps, e.g. from
+    /// [`TimeSeries::align_all_by`] and [`returns`].
+    ///
+    pub fn new(symbols: &[&str], returns: &[TimeSeries]) -> Self {
+        let n = returns.first().map_or(0, TimeSeries::len);
+        let means: Vec<f64> = returns
+            .iter()
+            .map(|r| r.values().iter().sum::<f64>() / n as f64)
+            .collect();
+        let covariance = |i: usize, j: usize| -> Option<f64> {
+            if n < 2 {
+                return None;
+            }
+            let (a, b) = (returns[i].values(), returns[j].values());
+            let sum = a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| (a - means[i]) * (b - means[j]))
+                .sum::<f64>();
+            Some(sum / (n - 1) as f64).filter(|c| c.is_finite())
+        };
+        let covariance: Vec<Vec<Option<f64>>> = (0..returns.len())
+            .map(|i| (0..returns.len()).map(|j| covariance(i, j)).collect())
+            .collect();
+        let correlation = (0..returns.len())
+            .map(|i| {
+                (0..returns.len())
+                    .map(
+                        |j| match (covariance[i][j], covariance[i][i], covariance[j][j]) {
+                            (Some(cov), Some(var_i), Some(var_j)) if var_i > 0.0 && var_j > 0.0 => {
+                                Some(cov / (var_i * var_j).sqrt())
+                            }
+                            _ => None,
+                        },
+                    )
+                    .collect()
+            })
+            .collect();
+
+        CorrelationMatrix {
+            end: returns.first().and_then(|r| r.last()).map(|(t, _)| t),
+            observations: n,
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            correlation,
+            covariance,
+        }
+    }
+
+    ///
+    /// The matrices of each `window` consecutive returns, see [`CorrelationMatrix::new`].
+    ///
+    /// # Returns
+    ///
+    /// One matrix per window, none if there are fewer than `window` returns.
+    ///
+    pub fn rolling(symbols: &[&str], returns: &[TimeSeries], window: usize) -> Vec<Self> {
+        let n = returns.first().map_or(0, TimeSeries::len);
+        if window == 0 || n < window {
+            return vec![];
+        }
+        (window..=n)
+            .map(|end| {
+                let windows: Vec<TimeSeries> = returns
+                    .iter()
+                    .map(|r| r.iter().skip(end - window).take(window).collect())
+                    .collect();
+                CorrelationMatrix::new(symbols, &windows)
+            })
+            .collect()
+    }
+
+    ///
+    /// CSV rows of `date,statistic,symbol` followed by one column per symbol, see [`csv_header`].
+    ///
+    pub fn to_csv(&self) -> String {
+        let date = self.end.map_or(String::new(), |end| end.to_rfc3339());
+        let mut csv = String::new();
+        for (statistic, matrix) in &[
+            ("correlation", &self.correlation),
+            ("covariance", &self.covariance),
+        ] {
+            for (symbol, row) in self.symbols.iter().zip(matrix.iter()) {
+                write!(csv, "{},{},{}", date, statistic, symbol).unwrap();
+                for value in row {
+                    match value {
+                        Some(value) => write!(csv, ",{:.6}", value).unwrap(),
+                        None => csv.push(','),
+                    }
+                }
+                csv.push('\n');
+            }
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "end": self.end.map(|end| end.to_rfc3339()),
+            "observations": self.observations,
+            "symbols": self.symbols,
+            "correlation": self.correlation,
+            "covariance": self.covariance,
+        })
+    }
+}
+
+///
+/// The CSV header of [`CorrelationMatrix::to_csv`].
+///
+pub fn csv_header(symbols: &[&str]) -> String {
+    format!("date,statistic,symbol,{}", symbols.join(","))
+}
+
+///
+/// The simple returns of a price series, each with the timestamp of the later price. The
+/// return after a zero price is undefined, NaN.
+///
+pub fn returns(prices: &TimeSeries) -> TimeSeries {
+    prices
+        .timestamps()
+        .iter()
+        .skip(1)
+        .copied()
+        .zip(prices.values().windows(2).map(|w| {
+            if w[0] != 0.0 {
+                w[1] / w[0] - 1.0
+            } else {
+                f64::NAN
+            }
+        }))
+        .collect()
+}
+
+///
+/// How a [`CorrelationMatrix`] is written.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MatrixFormat {
+    /// See [`CorrelationMatrix::to_csv`].
+    #[default]
+    Csv,
+    /// A JSON array of [`CorrelationMatrix::to_json`].
+    Json,
+}
+
+impl FromStr for MatrixFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(MatrixFormat::Csv),
+            "json" => Ok(MatrixFormat::Json),
+            _ => Err(format!(
+                "unknown matrix format '{}', expected csv or json",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::series::tests::{daily, day};
+
+    #[test]
+    fn test_correlation_matrix() {
+        let returns = vec![
+            returns(&daily(&[100.0, 110.0, 99.0, 118.8])),
+            returns(&daily(&[100.0, 90.0, 99.0, 79.2])),
+            returns(&daily(&[2.0, 2.0, 2.0, 2.0])),
+        ];
+        assert_eq!(returns[0].timestamps(), &[day(1), day(2), day(3)]);
+
+        let matrix = CorrelationMatrix::new(&["A", "B", "C"], &returns);
+        let close = |a: Option<f64>, b: f64| (a.unwrap() - b).abs() < 1e-12;
+        assert_eq!((matrix.end, matrix.observations), (Some(day(3)), 3));
+        assert!(close(matrix.correlation[0][0], 1.0));
+        assert!(close(matrix.correlation[0][1], -1.0));
+        assert!(close(matrix.correlation[1][0], -1.0));
+        assert_eq!(matrix.correlation[0][2], None);
+        // returns of 10%, -10% and 20%
+        assert!(close(matrix.covariance[0][0], 7.0 / 300.0));
+        assert!(close(matrix.covariance[0][1], -7.0 / 300.0));
+        assert_eq!(matrix.covariance[2][2], Some(0.0));
+
+        assert_eq!(
+            matrix.to_csv().lines().take(2).collect::<Vec<_>>(),
+            vec![
+                "2021-01-07T00:00:00+00:00,correlation,A,1.000000,-1.000000,",
+                "2021-01-07T00:00:00+00:00,correlation,B,-1.000000,1.000000,"
+            ]
+        );
+        assert_eq!(matrix.to_csv().lines().count(), 6);
+        assert_eq!(matrix.to_json()["correlation"][0][2], Value::Null);
+        assert_eq!(matrix.to_json()["symbols"], json!(["A", "B", "C"]));
+
+        // a zero price leaves the return after it undefined
+        let undefined = super::returns(&daily(&[0.0, 1.0, 2.0]));
+        assert!(undefined.values()[0].is_nan());
+        let matrix = CorrelationMatrix::new(&["A", "Z"], &[daily(&[0.1, 0.2]), undefined]);
+        assert!(close(matrix.correlation[0][0], 1.0));
+        assert_eq!(matrix.covariance[0][1], None);
+        assert_eq!(matrix.covariance[1][1], None);
+        assert_eq!(matrix.correlation[0][1], None);
+
+        let matrix = CorrelationMatrix::new(&["A"], &[daily(&[0.1])]);
+        assert_eq!(matrix.covariance, vec![vec![None]]);
+        assert_eq!(matrix.correlation, vec![vec![None]]);
+    }
+
+    #[test]
+    fn test_rolling_correlation_matrix() {
+        let returns = vec![
+            daily(&[0.1, -0.1, 0.2, 0.1]),
+            daily(&[0.2, -0.2, 0.4, -0.1]),
+        ];
+        let matrices = CorrelationMatrix::rolling(&["A", "B"], &returns, 3);
+        assert_eq!(matrices.len(), 2);
+        assert_eq!(matrices[0].end, Some(day(2)));
+        assert!((matrices[0].correlation[0][1].unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(matrices[1].end, Some(day(3)));
+        assert!(matrices[1].correlation[0][1].unwrap() < 1.0);
+        assert_eq!(
+            matrices[1],
+            CorrelationMatrix::new(
+                &["A", "B"],
+                &[
+                    daily(&[0.1, -0.1, 0.2, 0.1]).between(&day(1), &day(3)),
+                    daily(&[0.2, -0.2, 0.4, -0.1]).between(&day(1), &day(3)),
+                ]
+            )
+        );
+
+        assert!(CorrelationMatrix::rolling(&["A", "B"], &returns, 5).is_empty());
+        assert!(CorrelationMatrix::rolling(&["A", "B"], &returns, 0).is_empty());
+    }
+
+    #[test]
+    fn test_parse_matrix_format() {
+        assert_eq!("csv".parse(), Ok(MatrixFormat::Csv));
+        assert_eq!("json".parse(), Ok(MatrixFormat::Json));
+        assert!("xml".parse::<MatrixFormat>().is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use std::time::Duration;
+
+///
+/// Why fetching the quotes of a symbol failed.
+///
+#[derive(Debug)]
+pub enum FetchErrorKind {
+    /// The data source couldn't be reached or the connection failed.
+    Transport(String),
+    /// The data source rejected the request because of too many requests.
+    RateLimited { retry_after: Option<Duration> },
+    /// The data source failed with a server error (HTTP 5xx), which may be temporary.
+    Http(u16),
+    /// The data source rejected the request with another unexpected HTTP status code,
+    /// e.g. 400 or 403, which won't change when retried.
+    Rejected(u16),
+    /// The data source doesn't know the symbol.
+    UnknownSymbol,
+    /// The response couldn't be parsed or its content is inconsistent.
+    MalformedResponse(String),
+    /// The data source has no quotes for the requested period.
+    EmptyData,
+    /// The data source doesn't provide bars of the requested interval for the period.
+    InvalidRange(String),
+    /// Reading or writing local data (e.g. CSV files or the cache) failed.
+    Io(std::io::Error),
+}
+
+impl FetchErrorKind {
+    /// The names of all kinds, see [`FetchErrorKind::name`].
+    pub const NAMES: &'static [&'static str] = &[
+        "transport",
+        "rate-limited",
+        "http",
+        "rejected",
+        "unknown-symbol",
+        "malformed-response",
+        "empty-data",
+        "invalid-range",
+        "io",
+    ];
+
+    ///
+    /// A short name for the kind of error, e.g. to configure which errors are retried.
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Transport(_) => "transport",
+            Self::RateLimited { .. } => "rate-limited",
+            Self::Http(_) => "http",
+            Self::Rejected(_) => "rejected",
+            Self::UnknownSymbol => "unknown-symbol",
+            Self::MalformedResponse(_) => "malformed-response",
+            Self::EmptyData => "empty-data",
+            Self::InvalidRange(_) => "invalid-range",
+            Self::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for FetchErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(reason) => write!(f, "transport error: {}", reason),
+            Self::RateLimited {
+                retry_after: Some(retry_after),
+            } => write!(f, "rate limited (retry after {:?})", retry_after),
+            Self::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            Self::Http(status) => write!(f, "unexpected HTTP status {}", status),
+            Self::Rejected(status) => write!(f, "request rejected with HTTP status {}", status),
+            Self::UnknownSymbol => write!(f, "unknown symbol"),
+            Self::MalformedResponse(reason) => write!(f, "malformed response: {}", reason),
+            Self::EmptyData => write!(f, "no data"),
+            Self::InvalidRange(reason) => write!(f, "invalid range: {}", reason),
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+///
+/// An error while fetching the quotes of `symbol`.
+///
+#[derive(Debug)]
+pub struct FetchError {
+    pub symbol: String,
+    pub kind: FetchErrorKind,
+}
+
+impl FetchError {
+    pub fn new(symbol: &str, kind: FetchErrorKind) -> Self {
+        FetchError {
+            symbol: symbol.to_string(),
+            kind,
+        }
+    }
+
+    pub fn io(symbol: &str, error: std::io::Error) -> Self {
+        FetchError::new(symbol, FetchErrorKind::Io(error))
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fetching '{}' failed: {}", self.symbol, self.kind)
+    }
+}
+
+impl std::error::Error for FetchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            FetchErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
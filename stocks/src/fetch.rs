@@ -0,0 +1,311 @@
+use crate::actions::{self, CorporateAction};
+use crate::bar::{Bar, PriceBasis};
+use crate::error::{FetchError, FetchErrorKind};
+use crate::interval::Interval;
+use crate::provider::QuoteProvider;
+use crate::retry::RetryPolicy;
+use crate::series::TimeSeries;
+use chrono::prelude::*;
+
+///
+/// Retrieve the bars of `symbol` with one bar per `interval` from a data source,
+/// sorted by timestamp.
+/// Failed requests are retried according to `retry`, the last error is passed on from
+/// the provider. A period without any quotes is an error as well.
+///
+pub async fn fetch_bars(
+    provider: &dyn QuoteProvider,
+    retry: &RetryPolicy,
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    interval: Interval,
+) -> Result<Vec<Bar>, FetchError> {
+    let mut quotes = retry
+        .run(|| provider.history(symbol, beginning, end, interval))
+        .await?;
+    if !quotes.is_empty() {
+        quotes.sort_by_cached_key(|k| k.timestamp);
+        Ok(quotes.iter().map(Bar::from).collect())
+    } else {
+        Err(FetchError::new(symbol, FetchErrorKind::EmptyData))
+    }
+}
+
+///
+/// Retrieve data from a data source and extract the closing prices on `basis`,
+/// see [`fetch_bars`].
+///
+pub async fn fetch_closing_data(
+    provider: &dyn QuoteProvider,
+    retry: &RetryPolicy,
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    interval: Interval,
+    basis: PriceBasis,
+) -> Result<TimeSeries, FetchError> {
+    let bars = fetch_bars(provider, retry, symbol, beginning, end, interval).await?;
+    Ok(Bar::closes(&bars, basis))
+}
+
+///
+/// Retrieve the dividends and splits of `symbol`, sorted by timestamp.
+/// Failed requests are retried according to `retry`. A period without any actions is fine.
+///
+pub async fn fetch_actions(
+    provider: &dyn QuoteProvider,
+    retry: &RetryPolicy,
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Result<Vec<CorporateAction>, FetchError> {
+    let mut actions = retry
+        .run(|| provider.actions(symbol, beginning, end))
+        .await?;
+    actions.sort_by_key(|a| a.timestamp);
+    Ok(actions)
+}
+
+///
+/// Retrieve the daily bars and corporate actions of `symbol` and calculate its total
+/// return series, see [`actions::total_return`]. This is library API, none of the
+/// binaries report total returns.
+///
+pub async fn fetch_total_return(
+    provider: &dyn QuoteProvider,
+    retry: &RetryPolicy,
+    symbol: &str,
+    beginning: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Result<TimeSeries, FetchError> {
+    let bars = fetch_bars(provider, retry, symbol, beginning, end, Interval::OneDay).await?;
+    let actions = fetch_actions(provider, retry, symbol, beginning, end).await?;
+    Ok(actions::total_return(&bars, &actions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::ActionKind;
+    use crate::provider::tests::quote;
+    use crate::provider::{InMemoryProvider, Quote};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_fetch_closing_data() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", vec![quote(30, 3.0), quote(10, 1.0), quote(20, 2.0)])
+            .with_quotes("EMPTY", vec![]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(100, 0));
+        let retry = RetryPolicy::default();
+
+        assert_eq!(
+            aw!(fetch_closing_data(
+                &provider,
+                &retry,
+                "ABC",
+                &from,
+                &to,
+                Interval::OneDay,
+                PriceBasis::Adjusted
+            ))
+            .unwrap()
+            .iter()
+            .map(|(t, close)| (t.timestamp(), close))
+            .collect::<Vec<_>>(),
+            vec![(10, 1.0), (20, 2.0), (30, 3.0)]
+        );
+        assert!(matches!(
+            aw!(fetch_closing_data(
+                &provider,
+                &retry,
+                "EMPTY",
+                &from,
+                &to,
+                Interval::OneDay,
+                PriceBasis::Adjusted
+            ))
+            .unwrap_err()
+            .kind,
+            FetchErrorKind::EmptyData
+        ));
+        let error = aw!(fetch_closing_data(
+            &provider,
+            &retry,
+            "XYZ",
+            &from,
+            &to,
+            Interval::OneDay,
+            PriceBasis::Adjusted
+        ))
+        .unwrap_err();
+        assert_eq!(error.symbol, "XYZ");
+        assert!(matches!(error.kind, FetchErrorKind::UnknownSymbol));
+    }
+
+    #[test]
+    fn test_fetch_bars() {
+        let provider = InMemoryProvider::new().with_quotes(
+            "ABC",
+            vec![
+                Quote {
+                    volume: 200,
+                    ..quote(20, 2.0)
+                },
+                quote(10, 1.0),
+            ],
+        );
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(100, 0));
+
+        let bars = aw!(fetch_bars(
+            &provider,
+            &RetryPolicy::default(),
+            "ABC",
+            &from,
+            &to,
+            Interval::FiveMinutes
+        ))
+        .unwrap();
+        assert_eq!(
+            bars.iter()
+                .map(|b| (b.timestamp.timestamp(), b.adjclose, b.volume))
+                .collect::<Vec<_>>(),
+            vec![(10, 1.0, 0), (20, 2.0, 200)]
+        );
+    }
+
+    #[test]
+    fn test_fetch_total_return() {
+        let dividend = |ts, amount| CorporateAction {
+            timestamp: Utc.timestamp(ts, 0),
+            kind: ActionKind::Dividend { amount },
+        };
+        let provider = InMemoryProvider::new()
+            .with_quotes(
+                "ABC",
+                vec![quote(10, 10.0), quote(20, 10.0), quote(30, 11.0)],
+            )
+            .with_actions("ABC", vec![dividend(30, 1.0), dividend(20, 0.5)])
+            .with_actions("NOQUOTES", vec![dividend(20, 0.5)]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(100, 0));
+        let retry = RetryPolicy::default();
+
+        assert_eq!(
+            aw!(fetch_actions(&provider, &retry, "ABC", &from, &to)).unwrap(),
+            vec![dividend(20, 0.5), dividend(30, 1.0)]
+        );
+        assert_eq!(
+            aw!(fetch_total_return(&provider, &retry, "ABC", &from, &to))
+                .unwrap()
+                .values(),
+            &[10.0, 10.5, 12.6]
+        );
+        assert!(matches!(
+            aw!(fetch_total_return(
+                &provider, &retry, "NOQUOTES", &from, &to
+            ))
+            .unwrap_err()
+            .kind,
+            FetchErrorKind::UnknownSymbol
+        ));
+    }
+
+    ///
+    /// Fails `failures` times with a transport error before returning the wrapped provider's quotes.
+    ///
+    struct FlakyProvider {
+        inner: InMemoryProvider,
+        failures: u32,
+        attempts: AtomicU32,
+    }
+
+    #[async_trait]
+    impl QuoteProvider for FlakyProvider {
+        async fn history(
+            &self,
+            symbol: &str,
+            start: &DateTime<Utc>,
+            end: &DateTime<Utc>,
+            interval: Interval,
+        ) -> Result<Vec<Quote>, FetchError> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(FetchError::new(
+                    symbol,
+                    FetchErrorKind::Transport("connection reset".to_string()),
+                ))
+            } else {
+                self.inner.history(symbol, start, end, interval).await
+            }
+        }
+    }
+
+    #[test]
+    fn test_fetch_closing_data_retries() {
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(100, 0));
+        let retry = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let flaky = |failures| FlakyProvider {
+            inner: InMemoryProvider::new().with_quotes("ABC", vec![quote(10, 1.0)]),
+            failures,
+            attempts: AtomicU32::new(0),
+        };
+
+        let provider = flaky(2);
+        assert_eq!(
+            aw!(fetch_closing_data(
+                &provider,
+                &retry,
+                "ABC",
+                &from,
+                &to,
+                Interval::OneDay,
+                PriceBasis::Adjusted
+            ))
+            .unwrap()
+            .values(),
+            &[1.0]
+        );
+        assert_eq!(provider.attempts.load(Ordering::SeqCst), 3);
+
+        let provider = flaky(3);
+        assert!(matches!(
+            aw!(fetch_closing_data(
+                &provider,
+                &retry,
+                "ABC",
+                &from,
+                &to,
+                Interval::OneDay,
+                PriceBasis::Adjusted
+            ))
+            .unwrap_err()
+            .kind,
+            FetchErrorKind::Transport(_)
+        ));
+        assert_eq!(provider.attempts.load(Ordering::SeqCst), 3);
+
+        let provider = flaky(1);
+        assert!(aw!(fetch_closing_data(
+            &provider,
+            &RetryPolicy::none(),
+            "ABC",
+            &from,
+            &to,
+            Interval::OneDay,
+            PriceBasis::Adjusted
+        ))
+        .is_err());
+    }
+}
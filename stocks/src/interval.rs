@@ -0,0 +1,127 @@
+use chrono::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// The period covered by a single bar.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Interval {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+    OneHour,
+    #[default]
+    OneDay,
+    OneWeek,
+}
+
+impl Interval {
+    /// The names of all intervals, see [`Interval::name`].
+    pub const NAMES: &'static [&'static str] = &["1m", "5m", "15m", "1h", "1d", "1wk"];
+
+    ///
+    /// The short name as used by yahoo! finance, e.g. `5m` or `1wk`.
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            Interval::OneMinute => "1m",
+            Interval::FiveMinutes => "5m",
+            Interval::FifteenMinutes => "15m",
+            Interval::OneHour => "1h",
+            Interval::OneDay => "1d",
+            Interval::OneWeek => "1wk",
+        }
+    }
+
+    ///
+    /// The number of bars in a year of trading, assuming 252 trading days of 6.5 hours.
+    ///
+    pub fn periods_per_year(&self) -> f64 {
+        match self {
+            Interval::OneWeek => 52.0,
+            Interval::OneDay => 252.0,
+            _ => 252.0 * 6.5 * 60.0 / self.duration().num_minutes() as f64,
+        }
+    }
+
+    ///
+    /// What bars of different symbols are matched by: the date for daily and weekly bars,
+    /// which start at different times of the day on different exchanges, and the timestamp
+    /// for intraday bars.
+    ///
+    pub fn alignment_key(&self, timestamp: &DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Interval::OneDay | Interval::OneWeek => timestamp.date().and_hms(0, 0, 0),
+            _ => *timestamp,
+        }
+    }
+
+    pub fn duration(&self) -> chrono::Duration {
+        match self {
+            Interval::OneMinute => chrono::Duration::minutes(1),
+            Interval::FiveMinutes => chrono::Duration::minutes(5),
+            Interval::FifteenMinutes => chrono::Duration::minutes(15),
+            Interval::OneHour => chrono::Duration::hours(1),
+            Interval::OneDay => chrono::Duration::days(1),
+            Interval::OneWeek => chrono::Duration::weeks(1),
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1m" => Ok(Interval::OneMinute),
+            "5m" => Ok(Interval::FiveMinutes),
+            "15m" => Ok(Interval::FifteenMinutes),
+            "1h" => Ok(Interval::OneHour),
+            "1d" => Ok(Interval::OneDay),
+            "1wk" => Ok(Interval::OneWeek),
+            _ => Err(format!(
+                "unknown interval '{}', expected one of {}",
+                s,
+                Interval::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        for name in Interval::NAMES {
+            assert_eq!(name.parse::<Interval>().unwrap().name(), *name);
+        }
+        assert_eq!("1wk".parse(), Ok(Interval::OneWeek));
+        assert!("1w".parse::<Interval>().is_err());
+    }
+
+    #[test]
+    fn test_alignment_key() {
+        let t = Utc.ymd(2021, 1, 4).and_hms(14, 30, 0);
+        assert_eq!(
+            Interval::OneDay.alignment_key(&t),
+            Utc.ymd(2021, 1, 4).and_hms(0, 0, 0)
+        );
+        assert_eq!(Interval::OneHour.alignment_key(&t), t);
+    }
+
+    #[test]
+    fn test_periods_per_year() {
+        assert_eq!(Interval::OneDay.periods_per_year(), 252.0);
+        assert_eq!(Interval::OneHour.periods_per_year(), 1638.0);
+        assert_eq!(Interval::FiveMinutes.periods_per_year(), 19656.0);
+    }
+}
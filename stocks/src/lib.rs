@@ -0,0 +1,39 @@
+//!
+//! Stock signals, data retrieval and reporting shared by the
+//! `sync-to-async` and `async-on-timer` binaries.
+//!
+//! Using https://docs.rs/async-std/1.9.0/async_std/ for async
+//!
+
+pub mod actions;
+pub mod bar;
+pub mod cli;
+pub mod correlation;
+pub mod error;
+pub mod fetch;
+pub mod interval;
+#[cfg(any(test, feature = "mock-server"))]
+pub mod mock;
+pub mod provider;
+pub mod report;
+pub mod retry;
+pub mod series;
+pub mod signals;
+
+pub use actions::{ActionKind, CorporateAction};
+pub use bar::{Bar, PriceBasis};
+pub use correlation::{CorrelationMatrix, MatrixFormat};
+pub use error::{FetchError, FetchErrorKind};
+pub use fetch::{fetch_actions, fetch_bars, fetch_closing_data, fetch_total_return};
+pub use interval::Interval;
+pub use provider::{
+    CachedProvider, CassettePlayer, CassetteRecorder, CsvProvider, InMemoryProvider, QuoteProvider,
+    YahooProvider,
+};
+pub use retry::RetryPolicy;
+pub use series::TimeSeries;
+pub use signals::{
+    BenchmarkComparison, BenchmarkStats, BollingerBands, BollingerSeries, Crossover, Drawdown,
+    ExponentialMA, Macd, MacdSeries, MaxDrawdown, MaxPrice, MinPrice, Performance,
+    PerformanceStats, PriceDifference, RollingVolatility, Rsi, StockSignal, WindowedSMA,
+};
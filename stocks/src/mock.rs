@@ -0,0 +1,168 @@
+//!
+//! A local stand-in for the yahoo! finance chart API, e.g. for end-to-end tests.
+//! Point a [`YahooProvider`](crate::YahooProvider) (or `--yahoo-url`) at [`MockYahoo::url`].
+//!
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+///
+/// The body yahoo! finance sends with a 404 for unknown symbols.
+///
+pub const NOT_FOUND_BODY: &str = r#"{"chart":{"result":null,"error":{"code":"Not Found","description":"No data found, symbol may be delisted"}}}"#;
+
+///
+/// A canned HTTP response.
+///
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: &str) -> Self {
+        MockResponse {
+            status,
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+
+    ///
+    /// A 200 response with the content of a recorded chart API response.
+    ///
+    pub fn fixture<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(MockResponse::new(200, &std::fs::read_to_string(path)?))
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+///
+/// Serves the chart API (`/<SYMBOL>?...`) on a random local port until it is dropped.
+///
+/// Each symbol has a list of responses that are returned in order, the last one is
+/// repeated. Unknown symbols get a 404 like from yahoo! finance.
+///
+pub struct MockYahoo {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl MockYahoo {
+    pub fn start(routes: HashMap<String, Vec<MockResponse>>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(vec![]));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let (server_requests, server_stopped) = (requests.clone(), stopped.clone());
+        thread::spawn(move || {
+            let mut routes = routes;
+            for stream in listener.incoming() {
+                if server_stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    // a broken connection only fails the request that used it
+                    let _ = serve(stream, &mut routes, &server_requests);
+                }
+            }
+        });
+        Ok(MockYahoo {
+            addr,
+            requests,
+            stopped,
+        })
+    }
+
+    ///
+    /// The base URL to use instead of the chart API's.
+    ///
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    ///
+    /// The paths (including the query) of all requests so far.
+    ///
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockYahoo {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wake up the accept loop
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve(
+    mut stream: TcpStream,
+    routes: &mut HashMap<String, Vec<MockResponse>>,
+    requests: &Mutex<Vec<String>>,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // skip the headers, a GET has no body
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let target = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    let symbol = target
+        .trim_start_matches('/')
+        .split(['?', '/'])
+        .next()
+        .unwrap_or("");
+    let response = match routes.get_mut(symbol) {
+        Some(responses) if responses.len() > 1 => responses.remove(0),
+        Some(responses) if !responses.is_empty() => responses[0].clone(),
+        _ => MockResponse::new(404, NOT_FOUND_BODY),
+    };
+    requests.lock().unwrap().push(target);
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.body.len()
+    )?;
+    for (name, value) in &response.headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    write!(stream, "\r\n{}", response.body)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        403 => "Forbidden",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
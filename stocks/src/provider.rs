@@ -0,0 +1,123 @@
+use crate::actions::CorporateAction;
+use crate::error::{FetchError, FetchErrorKind};
+use crate::interval::Interval;
+use async_trait::async_trait;
+use chrono::prelude::*;
+
+mod cache;
+mod cassette;
+mod csv;
+mod memory;
+mod yahoo;
+
+pub use self::cache::CachedProvider;
+pub use self::cassette::{CassettePlayer, CassetteRecorder};
+pub use self::csv::CsvProvider;
+pub use self::memory::InMemoryProvider;
+pub use self::yahoo::{YahooProvider, YCHART_URL};
+pub use yahoo_finance_api::Quote;
+
+///
+/// A source of historical quotes, e.g. a web API or a local data set.
+///
+#[async_trait]
+pub trait QuoteProvider: Send + Sync {
+    ///
+    /// Retrieve the quotes of `symbol` between `start` and `end` (inclusive), one per `interval`.
+    ///
+    /// # Returns
+    ///
+    /// The quotes in the order the source provides them, or a [`FetchError`] describing
+    /// why the source failed.
+    ///
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError>;
+
+    ///
+    /// Retrieve the dividends and splits of `symbol` between `start` and `end` (inclusive).
+    ///
+    /// # Returns
+    ///
+    /// The actions in any order. Sources without corporate actions return none.
+    ///
+    async fn actions(
+        &self,
+        _symbol: &str,
+        _start: &DateTime<Utc>,
+        _end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        Ok(vec![])
+    }
+}
+
+#[async_trait]
+impl<P: QuoteProvider + ?Sized> QuoteProvider for Box<P> {
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        (**self).history(symbol, start, end, interval).await
+    }
+
+    async fn actions(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        (**self).actions(symbol, start, end).await
+    }
+}
+
+///
+/// The name of a local file with the data of `symbol`: `<SYMBOL>.<extension>` for daily
+/// bars and `<SYMBOL>.<interval>.<extension>` for all others.
+///
+fn file_name(symbol: &str, interval: Interval, extension: &str) -> String {
+    match interval {
+        Interval::OneDay => format!("{}.{}", symbol, extension),
+        _ => format!("{}.{}.{}", symbol, interval, extension),
+    }
+}
+
+///
+/// Check that `symbol` can name a file in a directory, see [`file_name`].
+///
+/// # Returns
+///
+/// An unknown symbol error for a symbol with a path separator.
+///
+fn check_file_symbol(symbol: &str) -> Result<(), FetchError> {
+    if symbol.contains(['/', '\\']) {
+        return Err(FetchError::new(symbol, FetchErrorKind::UnknownSymbol));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    ///
+    /// A quote with all prices set to `adjclose`.
+    ///
+    pub(crate) fn quote(timestamp: u64, adjclose: f64) -> Quote {
+        Quote {
+            timestamp,
+            open: adjclose,
+            high: adjclose,
+            low: adjclose,
+            volume: 0,
+            close: adjclose,
+            adjclose,
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use super::{check_file_symbol, file_name, Quote, QuoteProvider};
+use crate::actions::CorporateAction;
+use crate::error::FetchError;
+use crate::interval::Interval;
+use async_std::fs;
+use async_std::prelude::*;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+const CACHE_EXTENSION: &str = "quotes";
+const RANGE_MARKER: &str = "#range";
+
+///
+/// Wraps another provider and keeps the quotes it returned on disk, so later requests
+/// only fetch the date ranges that aren't cached yet.
+///
+/// Every symbol has an append-only file `<dir>/<SYMBOL>.quotes` for daily bars (and e.g.
+/// `<dir>/<SYMBOL>.5m.quotes` for other intervals). Each fetch appends a
+/// `#range,<start>,<end>` line with the requested timestamps, followed by one
+/// `timestamp,open,high,low,close,adjclose,volume` line per quote. Since fetches
+/// always extend the cached range, the covered range is the union of those lines.
+/// Later quotes replace earlier ones with the same timestamp. Missing ranges are fetched
+/// up to (or from) the earliest (latest) cached quote, which keeps a source from failing
+/// on a range without any quotes and refreshes a quote that may have been incomplete
+/// (e.g. during trading hours).
+///
+pub struct CachedProvider<P> {
+    inner: P,
+    dir: PathBuf,
+}
+
+impl<P: QuoteProvider> CachedProvider<P> {
+    pub fn new<D: Into<PathBuf>>(inner: P, dir: D) -> Self {
+        CachedProvider {
+            inner,
+            dir: dir.into(),
+        }
+    }
+
+    ///
+    /// Remove all cached quotes.
+    ///
+    pub async fn purge(&self) -> std::io::Result<()> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        while let Some(entry) = entries.next().await {
+            let path = entry?.path();
+            if path.extension() == Some(CACHE_EXTENSION.as_ref()) {
+                fs::remove_file(path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, symbol: &str, interval: Interval) -> PathBuf {
+        self.dir.join(file_name(symbol, interval, CACHE_EXTENSION))
+    }
+
+    async fn load(&self, symbol: &str, interval: Interval) -> Result<CacheEntry, FetchError> {
+        match fs::read_to_string(self.path(symbol, interval)).await {
+            Ok(content) => CacheEntry::parse(&content),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(CacheEntry::default()),
+            Err(e) => Err(e),
+        }
+        .map_err(|e| FetchError::io(symbol, e))
+    }
+
+    ///
+    /// Fetch `start..=end` from the inner provider and append the result to the cache.
+    ///
+    async fn fetch(
+        &self,
+        entry: &mut CacheEntry,
+        symbol: &str,
+        start: i64,
+        end: i64,
+        interval: Interval,
+    ) -> Result<(), FetchError> {
+        let quotes = self
+            .inner
+            .history(
+                symbol,
+                &Utc.timestamp(start, 0),
+                &Utc.timestamp(end, 0),
+                interval,
+            )
+            .await?;
+
+        let mut lines = format!("{},{},{}\n", RANGE_MARKER, start, end);
+        for q in &quotes {
+            lines.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                q.timestamp, q.open, q.high, q.low, q.close, q.adjclose, q.volume
+            ));
+        }
+        self.append(symbol, interval, &lines)
+            .await
+            .map_err(|e| FetchError::io(symbol, e))?;
+
+        entry.add_range(start, end);
+        for q in quotes {
+            entry.quotes.insert(q.timestamp, q);
+        }
+        Ok(())
+    }
+
+    async fn append(&self, symbol: &str, interval: Interval, lines: &str) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(symbol, interval))
+            .await?;
+        // a single write per fetch keeps the file consistent
+        file.write_all(lines.as_bytes()).await?;
+        file.flush().await
+    }
+}
+
+#[async_trait]
+impl<P: QuoteProvider> QuoteProvider for CachedProvider<P> {
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        // the symbol names the cache file
+        check_file_symbol(symbol)?;
+        let (start, end) = (start.timestamp(), end.timestamp());
+        let mut entry = self.load(symbol, interval).await?;
+
+        match entry.range {
+            None => self.fetch(&mut entry, symbol, start, end, interval).await?,
+            Some((cached_start, cached_end)) => {
+                if start < cached_start {
+                    let earliest = entry
+                        .quotes
+                        .keys()
+                        .next()
+                        .map_or(cached_start, |ts| *ts as i64);
+                    self.fetch(&mut entry, symbol, start, earliest, interval)
+                        .await?;
+                }
+                if end > cached_end {
+                    let latest = entry
+                        .quotes
+                        .keys()
+                        .next_back()
+                        .map_or(cached_end, |ts| (*ts as i64).min(cached_end));
+                    self.fetch(&mut entry, symbol, latest, end, interval)
+                        .await?;
+                }
+            }
+        }
+        Ok(entry
+            .quotes
+            .range(start.max(0) as u64..=end.max(0) as u64)
+            .map(|(_, q)| q.clone())
+            .collect())
+    }
+
+    ///
+    /// Corporate actions are rare and may be announced after the fact, so they aren't cached.
+    ///
+    async fn actions(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        self.inner.actions(symbol, start, end).await
+    }
+}
+
+///
+/// The cached range and quotes of a symbol, sorted by timestamp.
+///
+#[derive(Debug, Default)]
+struct CacheEntry {
+    range: Option<(i64, i64)>,
+    quotes: BTreeMap<u64, Quote>,
+}
+
+impl CacheEntry {
+    fn parse(content: &str) -> std::io::Result<CacheEntry> {
+        let mut entry = CacheEntry::default();
+        for line in content.lines().filter(|l| !l.is_empty()) {
+            let invalid = || {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid cache line: '{}'", line),
+                )
+            };
+            let cols: Vec<&str> = line.split(',').collect();
+            match cols.as_slice() {
+                [RANGE_MARKER, start, end] => entry.add_range(
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                [timestamp, open, high, low, close, adjclose, volume] => {
+                    let price = |s: &str| s.parse::<f64>().map_err(|_| invalid());
+                    let quote = Quote {
+                        timestamp: timestamp.parse().map_err(|_| invalid())?,
+                        open: price(open)?,
+                        high: price(high)?,
+                        low: price(low)?,
+                        close: price(close)?,
+                        adjclose: price(adjclose)?,
+                        volume: volume.parse().map_err(|_| invalid())?,
+                    };
+                    entry.quotes.insert(quote.timestamp, quote);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(entry)
+    }
+
+    fn add_range(&mut self, start: i64, end: i64) {
+        self.range = Some(match self.range {
+            Some((s, e)) => (s.min(start), e.max(end)),
+            None => (start, end),
+        });
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::error::FetchErrorKind;
+    use crate::provider::tests::quote;
+    use crate::provider::InMemoryProvider;
+    use std::sync::Mutex;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    ///
+    /// Records the ranges that were requested from the wrapped provider.
+    ///
+    struct RecordingProvider {
+        inner: InMemoryProvider,
+        requests: Mutex<Vec<(i64, i64)>>,
+    }
+
+    #[async_trait]
+    impl QuoteProvider for RecordingProvider {
+        async fn history(
+            &self,
+            symbol: &str,
+            start: &DateTime<Utc>,
+            end: &DateTime<Utc>,
+            interval: Interval,
+        ) -> Result<Vec<Quote>, FetchError> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((start.timestamp(), end.timestamp()));
+            self.inner.history(symbol, start, end, interval).await
+        }
+    }
+
+    fn closes(quotes: Vec<Quote>) -> Vec<f64> {
+        quotes.iter().map(|q| q.adjclose).collect()
+    }
+
+    #[test]
+    fn test_CachedProvider_history() {
+        let dir = std::env::temp_dir().join(format!("stocks-cache-{}", std::process::id()));
+        let inner = InMemoryProvider::new()
+            .with_quotes("ABC", (0..10).map(|i| quote(i * 10, i as f64)).collect());
+        let provider = CachedProvider::new(
+            RecordingProvider {
+                inner,
+                requests: Mutex::new(vec![]),
+            },
+            &dir,
+        );
+        let history = |start, end| {
+            aw!(provider.history(
+                "ABC",
+                &Utc.timestamp(start, 0),
+                &Utc.timestamp(end, 0),
+                Interval::OneDay
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(closes(history(30, 55)), vec![3.0, 4.0, 5.0]);
+        assert_eq!(closes(history(30, 50)), vec![3.0, 4.0, 5.0]);
+        assert_eq!(closes(history(40, 55)), vec![4.0, 5.0]);
+        assert_eq!(*provider.inner.requests.lock().unwrap(), vec![(30, 55)]);
+
+        assert_eq!(
+            closes(history(10, 75)),
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]
+        );
+        assert_eq!(
+            *provider.inner.requests.lock().unwrap(),
+            vec![(30, 55), (10, 30), (50, 75)]
+        );
+
+        // the cached range is served from disk, even without the original provider
+        let cached = CachedProvider::new(InMemoryProvider::new(), &dir);
+        let (start, end) = (Utc.timestamp(20, 0), Utc.timestamp(75, 0));
+        let quotes = aw!(cached.history("ABC", &start, &end, Interval::OneDay));
+        assert_eq!(closes(quotes.unwrap()), vec![2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+
+        // other intervals are cached separately
+        let quotes = aw!(provider.history("ABC", &start, &end, Interval::OneHour));
+        assert_eq!(closes(quotes.unwrap()), vec![2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        assert_eq!(
+            provider.inner.requests.lock().unwrap().last(),
+            Some(&(20, 75))
+        );
+        assert!(dir.join("ABC.1h.quotes").exists());
+
+        let error = aw!(provider.history("../ABC", &start, &end, Interval::OneDay)).unwrap_err();
+        assert!(matches!(error.kind, FetchErrorKind::UnknownSymbol));
+        assert!(!dir.join("../ABC.quotes").exists());
+        assert_eq!(
+            provider.inner.requests.lock().unwrap().last(),
+            Some(&(20, 75))
+        );
+
+        aw!(provider.purge()).unwrap();
+        assert!(!dir.join("ABC.quotes").exists());
+        assert!(!dir.join("ABC.1h.quotes").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
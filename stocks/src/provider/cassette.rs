@@ -0,0 +1,471 @@
+use super::{Quote, QuoteProvider};
+use crate::actions::{ActionKind, CorporateAction};
+use crate::error::{FetchError, FetchErrorKind};
+use crate::interval::Interval;
+use async_std::fs;
+use async_std::prelude::*;
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const HISTORY_MARKER: &str = "#history";
+const ACTIONS_MARKER: &str = "#actions";
+const ERROR_MARKER: &str = "#error";
+
+///
+/// Wraps another provider and writes every response it returns to a cassette file,
+/// which a [`CassettePlayer`] replays later without the original provider.
+///
+/// Each response starts with a `#history,<SYMBOL>,<interval>,<start>,<end>` or
+/// `#actions,<SYMBOL>,<start>,<end>` line with the request, followed by either one
+/// `timestamp,open,high,low,close,adjclose,volume` line per quote, one
+/// `<ex-date>,dividend,<amount>` or `<ex-date>,split,<numerator>:<denominator>` line per
+/// action, or a single `#error,<kind>,<detail>` line. Failed attempts are recorded too,
+/// so retries are replayed as they happened.
+///
+pub struct CassetteRecorder<P> {
+    inner: P,
+    file: Mutex<fs::File>,
+}
+
+impl<P: QuoteProvider> CassetteRecorder<P> {
+    ///
+    /// Create (or truncate) the cassette at `path`.
+    ///
+    pub async fn create<F: Into<PathBuf>>(inner: P, path: F) -> std::io::Result<Self> {
+        let file = fs::File::create(path.into()).await?;
+        Ok(CassetteRecorder {
+            inner,
+            file: Mutex::new(file),
+        })
+    }
+
+    async fn record(
+        &self,
+        symbol: &str,
+        mut lines: String,
+        error: Option<&FetchError>,
+    ) -> Result<(), FetchError> {
+        if let Some(error) = error {
+            let (kind, detail) = encode_error(&error.kind);
+            lines.push_str(&format!("{},{},{}\n", ERROR_MARKER, kind, escape(&detail)));
+        }
+        let mut file = self.file.lock().await;
+        // a single write per response keeps concurrent fetches apart
+        file.write_all(lines.as_bytes())
+            .await
+            .map_err(|e| FetchError::io(symbol, e))?;
+        file.flush().await.map_err(|e| FetchError::io(symbol, e))
+    }
+}
+
+#[async_trait]
+impl<P: QuoteProvider> QuoteProvider for CassetteRecorder<P> {
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        let result = self.inner.history(symbol, start, end, interval).await;
+        let mut lines = format!(
+            "{},{},{},{},{}\n",
+            HISTORY_MARKER,
+            symbol,
+            interval,
+            start.timestamp(),
+            end.timestamp()
+        );
+        for q in result.iter().flatten() {
+            lines.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                q.timestamp, q.open, q.high, q.low, q.close, q.adjclose, q.volume
+            ));
+        }
+        self.record(symbol, lines, result.as_ref().err()).await?;
+        result
+    }
+
+    async fn actions(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        let result = self.inner.actions(symbol, start, end).await;
+        let mut lines = format!(
+            "{},{},{},{}\n",
+            ACTIONS_MARKER,
+            symbol,
+            start.timestamp(),
+            end.timestamp()
+        );
+        for action in result.iter().flatten() {
+            lines.push_str(&format!("{}\n", action));
+        }
+        self.record(symbol, lines, result.as_ref().err()).await?;
+        result
+    }
+}
+
+///
+/// A response read from a cassette.
+///
+#[derive(Clone, Debug)]
+enum Recorded {
+    Quotes(Vec<Quote>),
+    Actions(Vec<CorporateAction>),
+    Error(String, String),
+}
+
+///
+/// Replays the responses of a cassette written by a [`CassetteRecorder`], without any
+/// network access.
+///
+/// The responses for a symbol (and interval) are returned in the order they were recorded,
+/// the last one is repeated. The requested range isn't compared with the recorded one,
+/// since a run usually ends at the current time.
+///
+pub struct CassettePlayer {
+    path: PathBuf,
+    responses: std::sync::Mutex<HashMap<String, VecDeque<Recorded>>>,
+}
+
+impl CassettePlayer {
+    pub async fn open<F: Into<PathBuf>>(path: F) -> std::io::Result<Self> {
+        let path = path.into();
+        let content = fs::read_to_string(&path).await?;
+        Ok(CassettePlayer {
+            responses: std::sync::Mutex::new(parse(&content)?),
+            path,
+        })
+    }
+
+    fn next(&self, symbol: &str, key: &str) -> Result<Recorded, FetchError> {
+        let mut responses = self.responses.lock().unwrap();
+        let recorded = match responses.get_mut(key) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        };
+        let recorded = recorded.ok_or_else(|| {
+            let message = format!("no response for {} in {}", key, self.path.display());
+            FetchError::io(symbol, Error::new(ErrorKind::NotFound, message))
+        })?;
+        match recorded {
+            Recorded::Error(kind, detail) => Err(FetchError::new(
+                symbol,
+                decode_error(&kind, &detail).map_err(|e| FetchError::io(symbol, e))?,
+            )),
+            recorded => Ok(recorded),
+        }
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for CassettePlayer {
+    async fn history(
+        &self,
+        symbol: &str,
+        _start: &DateTime<Utc>,
+        _end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        match self.next(symbol, &history_key(symbol, interval))? {
+            Recorded::Quotes(quotes) => Ok(quotes),
+            _ => unreachable!("history keys only have quotes"),
+        }
+    }
+
+    async fn actions(
+        &self,
+        symbol: &str,
+        _start: &DateTime<Utc>,
+        _end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        match self.next(symbol, &actions_key(symbol))? {
+            Recorded::Actions(actions) => Ok(actions),
+            _ => unreachable!("actions keys only have actions"),
+        }
+    }
+}
+
+fn history_key(symbol: &str, interval: Interval) -> String {
+    format!("{} quotes of {}", interval, symbol)
+}
+
+fn actions_key(symbol: &str) -> String {
+    format!("actions of {}", symbol)
+}
+
+fn parse(content: &str) -> std::io::Result<HashMap<String, VecDeque<Recorded>>> {
+    let mut responses: HashMap<String, VecDeque<Recorded>> = HashMap::new();
+    let mut current: Option<(String, Recorded)> = None;
+    for line in content.lines().filter(|l| !l.is_empty()) {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid cassette line: '{}'", line),
+            )
+        };
+        let cols: Vec<&str> = line.splitn(3, ',').collect();
+        let started = match (cols[0], &mut current) {
+            (HISTORY_MARKER, _) => {
+                let cols: Vec<&str> = line.split(',').collect();
+                let interval = cols.get(2).and_then(|i| i.parse().ok());
+                match (cols.len(), interval) {
+                    (5, Some(interval)) => {
+                        Some((history_key(cols[1], interval), Recorded::Quotes(vec![])))
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            (ACTIONS_MARKER, _) => match line.split(',').count() {
+                4 => Some((actions_key(cols[1]), Recorded::Actions(vec![]))),
+                _ => return Err(invalid()),
+            },
+            (ERROR_MARKER, Some((_, recorded))) if cols.len() == 3 => {
+                decode_error(cols[1], &unescape(cols[2]))?;
+                *recorded = Recorded::Error(cols[1].to_string(), unescape(cols[2]));
+                None
+            }
+            (_, Some((_, Recorded::Quotes(quotes)))) => {
+                quotes.push(parse_quote(line).ok_or_else(invalid)?);
+                None
+            }
+            (_, Some((_, Recorded::Actions(actions)))) => {
+                actions.push(parse_action(line).ok_or_else(invalid)?);
+                None
+            }
+            _ => return Err(invalid()),
+        };
+        if let Some(next) = started {
+            if let Some((key, recorded)) = current.replace(next) {
+                responses.entry(key).or_default().push_back(recorded);
+            }
+        }
+    }
+    if let Some((key, recorded)) = current {
+        responses.entry(key).or_default().push_back(recorded);
+    }
+    Ok(responses)
+}
+
+fn parse_quote(line: &str) -> Option<Quote> {
+    let cols: Vec<&str> = line.split(',').collect();
+    match cols.as_slice() {
+        [timestamp, open, high, low, close, adjclose, volume] => Some(Quote {
+            timestamp: timestamp.parse().ok()?,
+            open: open.parse().ok()?,
+            high: high.parse().ok()?,
+            low: low.parse().ok()?,
+            close: close.parse().ok()?,
+            adjclose: adjclose.parse().ok()?,
+            volume: volume.parse().ok()?,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_action(line: &str) -> Option<CorporateAction> {
+    let cols: Vec<&str> = line.split(',').collect();
+    let kind = match cols.as_slice() {
+        [_, "dividend", amount] => ActionKind::Dividend {
+            amount: amount.parse().ok()?,
+        },
+        [_, "split", ratio] => {
+            let (numerator, denominator) = ratio.split_at(ratio.find(':')?);
+            ActionKind::Split {
+                numerator: numerator.parse().ok()?,
+                denominator: denominator[1..].parse().ok()?,
+            }
+        }
+        _ => return None,
+    };
+    Some(CorporateAction {
+        timestamp: DateTime::parse_from_rfc3339(cols[0])
+            .ok()?
+            .with_timezone(&Utc),
+        kind,
+    })
+}
+
+///
+/// The name and the detail (e.g. the reason or the status code) of an error.
+///
+fn encode_error(kind: &FetchErrorKind) -> (&'static str, String) {
+    let detail = match kind {
+        FetchErrorKind::Transport(reason)
+        | FetchErrorKind::MalformedResponse(reason)
+        | FetchErrorKind::InvalidRange(reason) => reason.clone(),
+        FetchErrorKind::RateLimited { retry_after } => retry_after
+            .map(|d| d.as_millis().to_string())
+            .unwrap_or_default(),
+        FetchErrorKind::Http(status) | FetchErrorKind::Rejected(status) => status.to_string(),
+        FetchErrorKind::UnknownSymbol | FetchErrorKind::EmptyData => String::new(),
+        FetchErrorKind::Io(e) => e.to_string(),
+    };
+    (kind.name(), detail)
+}
+
+fn decode_error(name: &str, detail: &str) -> std::io::Result<FetchErrorKind> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid recorded error: '{},{}'", name, detail),
+        )
+    };
+    Ok(match name {
+        "transport" => FetchErrorKind::Transport(detail.to_string()),
+        "rate-limited" if detail.is_empty() => FetchErrorKind::RateLimited { retry_after: None },
+        "rate-limited" => FetchErrorKind::RateLimited {
+            retry_after: Some(Duration::from_millis(
+                detail.parse().map_err(|_| invalid())?,
+            )),
+        },
+        "http" => FetchErrorKind::Http(detail.parse().map_err(|_| invalid())?),
+        "rejected" => FetchErrorKind::Rejected(detail.parse().map_err(|_| invalid())?),
+        "unknown-symbol" => FetchErrorKind::UnknownSymbol,
+        "malformed-response" => FetchErrorKind::MalformedResponse(detail.to_string()),
+        "empty-data" => FetchErrorKind::EmptyData,
+        "invalid-range" => FetchErrorKind::InvalidRange(detail.to_string()),
+        "io" => FetchErrorKind::Io(Error::other(detail)),
+        _ => return Err(invalid()),
+    })
+}
+
+///
+/// Keep a detail on a single line.
+///
+fn escape(detail: &str) -> String {
+    detail.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(detail: &str) -> String {
+    let mut unescaped = String::with_capacity(detail.len());
+    let mut chars = detail.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::provider::tests::quote;
+    use crate::provider::InMemoryProvider;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_CassettePlayer_replays_recording() {
+        let path = std::env::temp_dir().join(format!("stocks-cassette-{}", std::process::id()));
+        let inner = InMemoryProvider::new()
+            .with_quotes("ABC", vec![quote(10, 1.5), quote(20, 2.25)])
+            .with_actions(
+                "ABC",
+                vec![CorporateAction {
+                    timestamp: Utc.timestamp(20, 0),
+                    kind: ActionKind::Split {
+                        numerator: 4.0,
+                        denominator: 1.0,
+                    },
+                }],
+            );
+        let (start, end) = (Utc.timestamp(0, 0), Utc.timestamp(30, 0));
+
+        let recorder = aw!(CassetteRecorder::create(inner.clone(), &path)).unwrap();
+        let quotes = aw!(recorder.history("ABC", &start, &end, Interval::OneDay)).unwrap();
+        let actions = aw!(recorder.actions("ABC", &start, &end)).unwrap();
+        assert!(aw!(recorder.history("XYZ", &start, &end, Interval::OneDay)).is_err());
+        let error = FetchError::new(
+            "ABC",
+            FetchErrorKind::MalformedResponse("line 1\nline 2 \\n".to_string()),
+        );
+        aw!(recorder.record("ABC", "#history,ABC,1h,0,30\n".to_string(), Some(&error))).unwrap();
+
+        let player = aw!(CassettePlayer::open(&path)).unwrap();
+        // the range isn't part of the recorded request
+        let later = Utc.timestamp(40, 0);
+        assert_eq!(
+            aw!(player.history("ABC", &start, &later, Interval::OneDay)).unwrap(),
+            quotes
+        );
+        assert_eq!(aw!(player.actions("ABC", &start, &end)).unwrap(), actions);
+        assert!(matches!(
+            aw!(player.history("XYZ", &start, &end, Interval::OneDay))
+                .unwrap_err()
+                .kind,
+            FetchErrorKind::UnknownSymbol
+        ));
+        assert_eq!(
+            aw!(player.history("ABC", &start, &end, Interval::OneHour))
+                .unwrap_err()
+                .kind
+                .to_string(),
+            error.kind.to_string()
+        );
+        // nothing was recorded for weekly bars
+        assert!(matches!(
+            aw!(player.history("ABC", &start, &end, Interval::OneWeek))
+                .unwrap_err()
+                .kind,
+            FetchErrorKind::Io(_)
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_CassettePlayer_replays_in_order() {
+        let content = "#history,ABC,1d,0,30\n\
+                       #error,http,503\n\
+                       #history,ABC,1d,0,30\n\
+                       #error,rate-limited,1500\n\
+                       #history,ABC,1d,0,30\n\
+                       10,1.5,1.5,1.5,1.5,1.5,0\n";
+        let player = CassettePlayer {
+            path: PathBuf::from("test"),
+            responses: std::sync::Mutex::new(parse(content).unwrap()),
+        };
+        let history =
+            || aw!(player.history("ABC", &Utc.timestamp(0, 0), &Utc::now(), Interval::OneDay));
+
+        assert!(matches!(
+            history().unwrap_err().kind,
+            FetchErrorKind::Http(503)
+        ));
+        assert!(matches!(
+            history().unwrap_err().kind,
+            FetchErrorKind::RateLimited {
+                retry_after: Some(d)
+            } if d == Duration::from_millis(1500)
+        ));
+        assert_eq!(history().unwrap(), vec![quote(10, 1.5)]);
+        assert_eq!(history().unwrap(), vec![quote(10, 1.5)]);
+
+        assert!(parse("10,1.5,1.5,1.5,1.5,1.5,0\n").is_err());
+        assert!(parse("#history,ABC,2d,0,30\n").is_err());
+        assert!(parse("#actions,ABC,0,30\n#error,timeout,\n").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use super::{check_file_symbol, file_name, Quote, QuoteProvider};
+use crate::error::{FetchError, FetchErrorKind};
+use crate::interval::Interval;
+use async_std::fs;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::convert::TryFrom;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+///
+/// Quotes from local CSV files, one per symbol: `<dir>/<SYMBOL>.csv` for daily bars
+/// and e.g. `<dir>/<SYMBOL>.5m.csv` for other intervals.
+///
+/// Each file has the columns `date,open,high,low,close,adjclose,volume` with an optional
+/// header line (e.g. a yahoo! finance export). Dates are either `YYYY-MM-DD` (taken as
+/// midnight UTC) or RFC 3339 timestamps. Rows without a closing price (`null`) are skipped,
+/// missing (`null`) open, high, low or adjusted closing prices are taken from the close.
+///
+pub struct CsvProvider {
+    dir: PathBuf,
+}
+
+impl CsvProvider {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        CsvProvider { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for CsvProvider {
+    ///
+    /// A missing file (or a symbol with a path separator, which can't name a file in the
+    /// directory) is reported as an unknown symbol, rows that can't be parsed as a
+    /// malformed response.
+    ///
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        check_file_symbol(symbol)?;
+        let content = fs::read_to_string(self.dir.join(file_name(symbol, interval, "csv")))
+            .await
+            .map_err(|e| match e.kind() {
+                ErrorKind::NotFound => FetchError::new(symbol, FetchErrorKind::UnknownSymbol),
+                _ => FetchError::io(symbol, e),
+            })?;
+        let (start, end) = (start.timestamp(), end.timestamp());
+        let mut quotes = vec![];
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (i == 0 && line.to_lowercase().starts_with("date")) {
+                continue;
+            }
+            let row = parse_row(line)
+                .map_err(|e| FetchError::new(symbol, FetchErrorKind::MalformedResponse(e)))?;
+            if let Some(quote) = row {
+                if (start..=end).contains(&(quote.timestamp as i64)) {
+                    quotes.push(quote);
+                }
+            }
+        }
+        Ok(quotes)
+    }
+}
+
+///
+/// Parse a row of `date,open,high,low,close,adjclose,volume`.
+///
+/// # Returns
+///
+/// The quote, `None` if there is no closing price, or an error message.
+///
+fn parse_row(line: &str) -> Result<Option<Quote>, String> {
+    let invalid = || format!("invalid CSV row: '{}'", line);
+    let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+    if cols.len() != 7 {
+        return Err(invalid());
+    }
+    let price = |s: &str| -> Result<Option<f64>, String> {
+        if s == "null" {
+            Ok(None)
+        } else {
+            s.parse().map(Some).map_err(|_| invalid())
+        }
+    };
+    let close = match price(cols[4])? {
+        Some(close) => close,
+        None => return Ok(None),
+    };
+    let or_close = |s: &str| price(s).map(|p| p.unwrap_or(close));
+    // quotes can't be before 1970
+    let timestamp = parse_date(cols[0])
+        .and_then(|t| u64::try_from(t.timestamp()).ok())
+        .ok_or_else(invalid)?;
+    let volume = if cols[6] == "null" {
+        0
+    } else {
+        cols[6].parse().map_err(|_| invalid())?
+    };
+    Ok(Some(Quote {
+        timestamp,
+        open: or_close(cols[1])?,
+        high: or_close(cols[2])?,
+        low: or_close(cols[3])?,
+        close,
+        adjclose: or_close(cols[5])?,
+        volume,
+    }))
+}
+
+fn parse_date(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|d| Utc.from_utc_date(&d).and_hms(0, 0, 0))
+        .or_else(|_| s.parse::<DateTime<Utc>>())
+        .ok()
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_parse_row() {
+        assert_eq!(
+            parse_row("2021-01-04,1.5,2.5,1.0,2.0,1.9,1000").unwrap(),
+            Some(Quote {
+                timestamp: 1609718400,
+                open: 1.5,
+                high: 2.5,
+                low: 1.0,
+                close: 2.0,
+                adjclose: 1.9,
+                volume: 1000,
+            })
+        );
+        assert_eq!(
+            parse_row("2021-01-04T00:00:00Z,1,1,1,1,1,1")
+                .unwrap()
+                .map(|q| q.timestamp),
+            Some(1609718400)
+        );
+        assert_eq!(
+            parse_row("2021-01-04,null,null,null,null,null,null").unwrap(),
+            None
+        );
+        assert_eq!(
+            parse_row("2021-01-04,null,2.5,null,2.0,null,null").unwrap(),
+            Some(Quote {
+                timestamp: 1609718400,
+                open: 2.0,
+                high: 2.5,
+                low: 2.0,
+                close: 2.0,
+                adjclose: 2.0,
+                volume: 0,
+            })
+        );
+        assert!(parse_row("2021-01-04,1,1,1,1,1").is_err());
+        assert!(parse_row("yesterday,1,1,1,1,1,1").is_err());
+        assert!(parse_row("1969-12-31,1,1,1,1,1,1").is_err());
+        assert!(parse_row("2021-01-04,1,1,1,one,1,1").is_err());
+    }
+
+    #[test]
+    fn test_CsvProvider_history() {
+        let dir = std::env::temp_dir().join(format!("stocks-csv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("ABC.csv"),
+            "Date,Open,High,Low,Close,Adj Close,Volume\n\
+             2021-01-04,1,1,1,1,1,100\n\
+             2021-01-05,2,2,2,2,2,200\n\
+             2021-01-06,3,3,3,3,3,300\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("ABC.1h.csv"),
+            "2021-01-05T14:30:00Z,4,4,4,4,4,400\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::new(&dir);
+        let start = Utc.ymd(2021, 1, 5).and_hms(0, 0, 0);
+        let end = Utc.ymd(2021, 1, 31).and_hms(0, 0, 0);
+
+        let quotes = aw!(provider.history("ABC", &start, &end, Interval::OneDay)).unwrap();
+        assert_eq!(
+            quotes.iter().map(|q| q.adjclose).collect::<Vec<_>>(),
+            vec![2.0, 3.0]
+        );
+        let quotes = aw!(provider.history("ABC", &start, &end, Interval::OneHour)).unwrap();
+        assert_eq!(
+            quotes.iter().map(|q| q.adjclose).collect::<Vec<_>>(),
+            vec![4.0]
+        );
+        assert!(matches!(
+            aw!(provider.history("XYZ", &start, &end, Interval::OneDay))
+                .unwrap_err()
+                .kind,
+            FetchErrorKind::UnknownSymbol
+        ));
+        assert!(matches!(
+            aw!(provider.history("../ABC", &start, &end, Interval::OneDay))
+                .unwrap_err()
+                .kind,
+            FetchErrorKind::UnknownSymbol
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
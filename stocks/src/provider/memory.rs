@@ -0,0 +1,112 @@
+use super::{Quote, QuoteProvider};
+use crate::actions::CorporateAction;
+use crate::error::{FetchError, FetchErrorKind};
+use crate::interval::Interval;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::collections::HashMap;
+
+///
+/// Quotes kept in memory, e.g. for tests or data that has been loaded elsewhere.
+/// The quotes are returned as they are, whatever interval is requested.
+///
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryProvider {
+    quotes: HashMap<String, Vec<Quote>>,
+    actions: HashMap<String, Vec<CorporateAction>>,
+}
+
+impl InMemoryProvider {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///
+    /// Add (or replace) the quotes for `symbol`.
+    ///
+    pub fn with_quotes(mut self, symbol: &str, quotes: Vec<Quote>) -> Self {
+        self.quotes.insert(symbol.to_string(), quotes);
+        self
+    }
+
+    ///
+    /// Add (or replace) the dividends and splits for `symbol`.
+    ///
+    pub fn with_actions(mut self, symbol: &str, actions: Vec<CorporateAction>) -> Self {
+        self.actions.insert(symbol.to_string(), actions);
+        self
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for InMemoryProvider {
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        _interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        let (start, end) = (start.timestamp(), end.timestamp());
+        self.quotes
+            .get(symbol)
+            .map(|quotes| {
+                quotes
+                    .iter()
+                    .filter(|q| (start..=end).contains(&(q.timestamp as i64)))
+                    .cloned()
+                    .collect()
+            })
+            .ok_or_else(|| FetchError::new(symbol, FetchErrorKind::UnknownSymbol))
+    }
+
+    async fn actions(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        if !self.quotes.contains_key(symbol) && !self.actions.contains_key(symbol) {
+            return Err(FetchError::new(symbol, FetchErrorKind::UnknownSymbol));
+        }
+        Ok(self
+            .actions
+            .get(symbol)
+            .into_iter()
+            .flatten()
+            .filter(|a| (*start..=*end).contains(&a.timestamp))
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::provider::tests::quote;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_InMemoryProvider_history() {
+        let provider =
+            InMemoryProvider::new().with_quotes("ABC", vec![quote(10, 1.0), quote(20, 2.0)]);
+        let (start, end) = (Utc.timestamp(15, 0), Utc.timestamp(20, 0));
+
+        assert_eq!(
+            aw!(provider.history("ABC", &start, &end, Interval::OneDay)).unwrap(),
+            vec![quote(20, 2.0)]
+        );
+        assert!(matches!(
+            aw!(provider.history("XYZ", &start, &end, Interval::OneDay))
+                .unwrap_err()
+                .kind,
+            FetchErrorKind::UnknownSymbol
+        ));
+    }
+}
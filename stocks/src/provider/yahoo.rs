@@ -0,0 +1,422 @@
+use super::{Quote, QuoteProvider};
+use crate::actions::{ActionKind, CorporateAction};
+use crate::error::{FetchError, FetchErrorKind};
+use crate::interval::Interval;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use reqwest::{header, Response, StatusCode};
+use std::time::Duration;
+use tokio_compat_02::FutureExt;
+use yahoo_finance_api as yahoo;
+
+pub const YCHART_URL: &str = "https://query1.finance.yahoo.com/v8/finance/chart";
+
+///
+/// Quotes from the yahoo! finance chart API.
+///
+/// Sends the requests itself (instead of using `yahoo::YahooConnector`) to distinguish
+/// HTTP status codes and read the `Retry-After` header. The responses are parsed with
+/// `yahoo_finance_api`.
+///
+pub struct YahooProvider {
+    url: String,
+}
+
+impl YahooProvider {
+    pub fn new() -> Self {
+        Self::with_url(YCHART_URL)
+    }
+
+    ///
+    /// A provider for a chart API at another base URL, e.g. a mock server.
+    ///
+    pub fn with_url(url: &str) -> Self {
+        YahooProvider {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    ///
+    /// Check that yahoo! finance provides bars of `interval` between `start` and `end`:
+    /// 1m bars are kept for 30 days and can only be requested for 7 days at a time,
+    /// 5m and 15m bars are kept for 60 days and 1h bars for 730 days.
+    ///
+    /// # Returns
+    ///
+    /// An error message if the range exceeds those limits.
+    ///
+    pub fn check_range(
+        interval: Interval,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<(), String> {
+        check_range(interval, start, end, Utc::now())
+    }
+
+    ///
+    /// The longest range up to now that yahoo! finance serves bars of `interval` for in a
+    /// single request, see [`YahooProvider::check_range`]. A day shorter than the period
+    /// the bars are kept for, so that the range is still available while it's fetched.
+    ///
+    /// # Returns
+    ///
+    /// The duration, `None` if daily and weekly bars are available for any range.
+    ///
+    pub fn max_range(interval: Interval) -> Option<chrono::Duration> {
+        let (kept_days, max_request_days) = limits(interval)?;
+        let days = max_request_days.map_or(kept_days - 1, |days| days.min(kept_days - 1));
+        Some(chrono::Duration::days(days))
+    }
+}
+
+impl Default for YahooProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl YahooProvider {
+    ///
+    /// Request the chart of `symbol` between `start` and `end` with bars of `interval`.
+    /// `events` are extra `events` to include, e.g. `div|split`.
+    ///
+    async fn chart(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+        events: Option<&str>,
+    ) -> Result<serde_json::Value, FetchError> {
+        let mut url = format!(
+            "{url}/{symbol}?symbol={symbol}&period1={start}&period2={end}&interval={interval}",
+            url = self.url,
+            symbol = symbol,
+            start = start.timestamp(),
+            end = end.timestamp(),
+            interval = interval,
+        );
+        if let Some(events) = events {
+            url.push_str("&events=");
+            url.push_str(&events.replace('|', "%7C"));
+        }
+        // reqwest 0.10 needs a tokio 0.2 runtime, compat() provides one
+        let response = reqwest::get(&url)
+            .compat()
+            .await
+            .map_err(|e| FetchError::new(symbol, FetchErrorKind::Transport(e.to_string())))?;
+        let response = check_status(response).map_err(|kind| FetchError::new(symbol, kind))?;
+        response
+            .json()
+            .compat()
+            .await
+            .map_err(|e| FetchError::new(symbol, FetchErrorKind::MalformedResponse(e.to_string())))
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for YahooProvider {
+    async fn history(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        interval: Interval,
+    ) -> Result<Vec<Quote>, FetchError> {
+        check_range(interval, start, end, Utc::now())
+            .map_err(|reason| FetchError::new(symbol, FetchErrorKind::InvalidRange(reason)))?;
+        let json = self.chart(symbol, start, end, interval, None).await?;
+        // a period without trading has neither timestamps nor prices
+        if json.pointer("/chart/result/0").is_some()
+            && json.pointer("/chart/result/0/timestamp").is_none()
+        {
+            return Err(FetchError::new(symbol, FetchErrorKind::EmptyData));
+        }
+        // intraday charts have no adjusted prices
+        let adjusted = json
+            .pointer("/chart/result/0/indicators/adjclose")
+            .is_some();
+        let quotes = yahoo::YResponse::from_json(json)
+            .and_then(|response| response.quotes())
+            .map_err(|e| FetchError::new(symbol, error_kind(e)))?;
+        Ok(if adjusted {
+            quotes
+        } else {
+            quotes
+                .into_iter()
+                .map(|q| Quote {
+                    adjclose: q.close,
+                    ..q
+                })
+                .collect()
+        })
+    }
+
+    async fn actions(
+        &self,
+        symbol: &str,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> Result<Vec<CorporateAction>, FetchError> {
+        let json = self
+            .chart(symbol, start, end, Interval::OneDay, Some("div|split"))
+            .await?;
+        parse_actions(&json)
+            .map_err(|reason| FetchError::new(symbol, FetchErrorKind::MalformedResponse(reason)))
+    }
+}
+
+///
+/// Read the `events` of a chart response: `{"dividends": {"<ts>": {"amount", "date"}},
+/// "splits": {"<ts>": {"date", "numerator", "denominator"}}}`. Both are optional.
+///
+fn parse_actions(json: &serde_json::Value) -> Result<Vec<CorporateAction>, String> {
+    let result = json
+        .pointer("/chart/result/0")
+        .ok_or_else(|| "no chart result".to_string())?;
+    let events = |name| {
+        result
+            .pointer(&format!("/events/{}", name))
+            .and_then(|events| events.as_object())
+            .into_iter()
+            .flat_map(|events| events.values())
+    };
+    let number = |event: &serde_json::Value, field: &str| {
+        event[field]
+            .as_f64()
+            .ok_or_else(|| format!("missing or invalid '{}' in event {}", field, event))
+    };
+    let date = |event: &serde_json::Value| {
+        event["date"]
+            .as_i64()
+            .map(|ts| Utc.timestamp(ts, 0))
+            .ok_or_else(|| format!("missing or invalid 'date' in event {}", event))
+    };
+
+    let mut actions = vec![];
+    for event in events("dividends") {
+        actions.push(CorporateAction {
+            timestamp: date(event)?,
+            kind: ActionKind::Dividend {
+                amount: number(event, "amount")?,
+            },
+        });
+    }
+    for event in events("splits") {
+        actions.push(CorporateAction {
+            timestamp: date(event)?,
+            kind: ActionKind::Split {
+                numerator: number(event, "numerator")?,
+                denominator: number(event, "denominator")?,
+            },
+        });
+    }
+    Ok(actions)
+}
+
+///
+/// The days yahoo! finance keeps bars of `interval` for and the most days it serves them
+/// for in a single request, `None` if daily and weekly bars have no limits.
+///
+fn limits(interval: Interval) -> Option<(i64, Option<i64>)> {
+    match interval {
+        Interval::OneMinute => Some((30, Some(7))),
+        Interval::FiveMinutes | Interval::FifteenMinutes => Some((60, None)),
+        Interval::OneHour => Some((730, None)),
+        Interval::OneDay | Interval::OneWeek => None,
+    }
+}
+
+fn check_range(
+    interval: Interval,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), String> {
+    let (kept_days, max_request_days) = match limits(interval) {
+        Some(limits) => limits,
+        None => return Ok(()),
+    };
+    if *start < now - chrono::Duration::days(kept_days) {
+        return Err(format!(
+            "{} bars are only available for the last {} days",
+            interval, kept_days
+        ));
+    }
+    match max_request_days {
+        Some(days) if *end - *start > chrono::Duration::days(days) => Err(format!(
+            "{} bars can only be requested for {} days at a time",
+            interval, days
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_status(response: Response) -> Result<Response, FetchErrorKind> {
+    match response.status() {
+        StatusCode::OK => Ok(response),
+        StatusCode::NOT_FOUND => Err(FetchErrorKind::UnknownSymbol),
+        StatusCode::TOO_MANY_REQUESTS => Err(FetchErrorKind::RateLimited {
+            retry_after: response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, Utc::now())),
+        }),
+        status if status.is_server_error() => Err(FetchErrorKind::Http(status.as_u16())),
+        status => Err(FetchErrorKind::Rejected(status.as_u16())),
+    }
+}
+
+///
+/// Parse a `Retry-After` header: either a number of seconds or an HTTP date.
+///
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    match value.parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => DateTime::parse_from_rfc2822(value).ok().map(|date| {
+            (date.with_timezone(&Utc) - now)
+                .to_std()
+                .unwrap_or_default()
+        }),
+    }
+}
+
+///
+/// Map the errors of parsing a response onto [`FetchErrorKind`]s.
+///
+fn error_kind(error: yahoo::YahooError) -> FetchErrorKind {
+    match error {
+        yahoo::YahooError::ConnectionFailed | yahoo::YahooError::FetchFailed(_) => {
+            FetchErrorKind::Transport(error.to_string())
+        }
+        yahoo::YahooError::EmptyDataSet => FetchErrorKind::EmptyData,
+        yahoo::YahooError::DeserializeFailed(_)
+        | yahoo::YahooError::InvalidJson
+        | yahoo::YahooError::DataInconsistency => {
+            FetchErrorKind::MalformedResponse(error.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = Utc.ymd(2021, 1, 4).and_hms(12, 0, 0);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 04 Jan 2021 12:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Mon, 04 Jan 2021 11:00:00 GMT", now),
+            Some(Duration::from_secs(0))
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_check_range() {
+        let now = Utc.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        let days_ago = |days| now - chrono::Duration::days(days);
+
+        assert!(check_range(Interval::OneDay, &days_ago(10000), &now, now).is_ok());
+        assert!(check_range(Interval::OneWeek, &days_ago(10000), &now, now).is_ok());
+        assert!(check_range(Interval::OneHour, &days_ago(700), &now, now).is_ok());
+        assert!(check_range(Interval::OneHour, &days_ago(731), &now, now).is_err());
+        assert!(check_range(Interval::FiveMinutes, &days_ago(59), &now, now).is_ok());
+        assert!(check_range(Interval::FifteenMinutes, &days_ago(61), &now, now).is_err());
+        assert!(check_range(Interval::OneMinute, &days_ago(7), &now, now).is_ok());
+        assert_eq!(
+            check_range(Interval::OneMinute, &days_ago(8), &now, now),
+            Err("1m bars can only be requested for 7 days at a time".to_string())
+        );
+        assert!(check_range(Interval::OneMinute, &days_ago(20), &days_ago(15), now).is_ok());
+        assert!(check_range(Interval::OneMinute, &days_ago(31), &days_ago(28), now).is_err());
+    }
+
+    #[test]
+    fn test_max_range() {
+        let now = Utc::now();
+        for interval in &[
+            Interval::OneMinute,
+            Interval::FiveMinutes,
+            Interval::FifteenMinutes,
+            Interval::OneHour,
+        ] {
+            let max_range = YahooProvider::max_range(*interval).unwrap();
+            assert!(check_range(*interval, &(now - max_range), &now, now).is_ok());
+            assert!(check_range(
+                *interval,
+                &(now - max_range - chrono::Duration::days(2)),
+                &now,
+                now
+            )
+            .is_err());
+        }
+        assert_eq!(
+            YahooProvider::max_range(Interval::OneMinute),
+            Some(chrono::Duration::days(7))
+        );
+        assert_eq!(YahooProvider::max_range(Interval::OneDay), None);
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        let json = serde_json::json!({"chart": {"result": [{"events": {
+            "dividends": {"1604673000": {"amount": 0.205, "date": 1604673000}},
+            "splits": {"1598880600": {
+                "date": 1598880600, "numerator": 4, "denominator": 1, "splitRatio": "4:1"
+            }}
+        }}]}});
+        assert_eq!(
+            parse_actions(&json).unwrap(),
+            vec![
+                CorporateAction {
+                    timestamp: Utc.timestamp(1604673000, 0),
+                    kind: ActionKind::Dividend { amount: 0.205 },
+                },
+                CorporateAction {
+                    timestamp: Utc.timestamp(1598880600, 0),
+                    kind: ActionKind::Split {
+                        numerator: 4.0,
+                        denominator: 1.0
+                    },
+                },
+            ]
+        );
+
+        let no_events = serde_json::json!({"chart": {"result": [{"timestamp": []}]}});
+        assert_eq!(parse_actions(&no_events).unwrap(), vec![]);
+        let invalid = serde_json::json!({"chart": {"result": [{"events": {
+            "dividends": {"1604673000": {"date": 1604673000}}
+        }}]}});
+        assert!(parse_actions(&invalid).is_err());
+        assert!(parse_actions(&serde_json::json!({"chart": {"result": null}})).is_err());
+    }
+
+    #[test]
+    fn test_error_kind() {
+        use yahoo::YahooError;
+
+        assert!(matches!(
+            error_kind(YahooError::EmptyDataSet),
+            FetchErrorKind::EmptyData
+        ));
+        assert!(matches!(
+            error_kind(YahooError::DeserializeFailed("missing field".to_string())),
+            FetchErrorKind::MalformedResponse(_)
+        ));
+        assert!(matches!(
+            error_kind(YahooError::DataInconsistency),
+            FetchErrorKind::MalformedResponse(_)
+        ));
+    }
+}
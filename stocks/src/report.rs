@@ -0,0 +1,1346 @@
+use crate::bar::{Bar, PriceBasis};
+use crate::correlation::{self, CorrelationMatrix, MatrixFormat};
+use crate::error::FetchError;
+use crate::fetch::{fetch_actions, fetch_bars};
+use crate::interval::Interval;
+use crate::provider::QuoteProvider;
+use crate::retry::RetryPolicy;
+use crate::series::TimeSeries;
+use crate::signals::{
+    BenchmarkComparison, BenchmarkStats, BollingerBands, ExponentialMA, Macd, MaxPrice, MinPrice,
+    Performance, PerformanceStats, PriceDifference, RollingVolatility, Rsi, StockSignal,
+    WindowedSMA,
+};
+use chrono::prelude::*;
+use futures::future::{self, Future, FutureExt};
+use futures::stream::{self, StreamExt};
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+///
+/// A simple way to output a CSV header matching [`Report`]'s `Display` output
+/// for daily bars, see [`csv_header`].
+///
+pub const CSV_HEADER: &str = "period start,symbol,price,change %,min,max,30d avg";
+
+///
+/// The number of bars the moving average of a [`Report`] is calculated over.
+///
+pub const SMA_WINDOW: usize = 30;
+
+///
+/// The CSV header for a report with `options`: the moving average columns
+/// count bars, e.g. `30x5m avg` for 5 minute bars.
+///
+pub fn csv_header(options: &ReportOptions) -> String {
+    let mut header = "period start,symbol,price,change %,min,max".to_string();
+    if let Some(window) = options.volatility_window {
+        header.push_str(&format!(
+            ",{} volatility",
+            bar_count(window, options.interval)
+        ));
+    }
+    header.push_str(&format!(",{} avg", bar_count(SMA_WINDOW, options.interval)));
+    if let Some(span) = options.ema_span {
+        header.push_str(&format!(",{} ema", bar_count(span, options.interval)));
+    }
+    if let Some(period) = options.rsi_period {
+        header.push_str(&format!(",{} rsi", bar_count(period, options.interval)));
+    }
+    if let Some(bands) = options.bollinger {
+        header.push_str(&format!(
+            ",% from {} upper band",
+            bar_count(bands.window, options.interval)
+        ));
+    }
+    if let Some(benchmark) = &options.benchmark {
+        for column in &["beta", "alpha", "correlation", "excess return"] {
+            header.push_str(&format!(",{} vs {}", column, benchmark));
+        }
+    }
+    header
+}
+
+///
+/// `n` bars of `interval` as a column name, e.g. `30d` or `12x5m`.
+///
+fn bar_count(n: usize, interval: Interval) -> String {
+    match interval {
+        Interval::OneDay => format!("{}d", n),
+        _ => format!("{}x{}", n, interval),
+    }
+}
+
+///
+/// The CSV header of [`run_actions`]' output.
+///
+pub const ACTIONS_CSV_HEADER: &str = "symbol,date,action,value";
+
+///
+/// The CSV header of [`run_crossovers`]' output.
+///
+pub const CROSSOVERS_CSV_HEADER: &str = "symbol,date,crossover";
+
+///
+/// The CSV header of [`run_performance`]' output, matching [`PerformanceReport`]'s `Display`.
+///
+pub const PERFORMANCE_CSV_HEADER: &str =
+    "period start,symbol,cagr,sharpe,sortino,calmar,ulcer index";
+
+///
+/// A single row of the report: the signals calculated for a symbol over a period.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub period_start: DateTime<Utc>,
+    pub symbol: String,
+    pub last_price: f64,
+    pub pct_change: f64,
+    pub period_min: f64,
+    pub period_max: f64,
+    /// The last annualized volatility if requested, `Some(None)` if there are too few bars.
+    pub volatility: Option<Option<f64>>,
+    pub sma: f64,
+    /// The last exponential moving average if requested, `Some(None)` if there are too few bars.
+    pub ema: Option<Option<f64>>,
+    /// The last relative strength index if requested, `Some(None)` if there are too few bars.
+    pub rsi: Option<Option<f64>>,
+    /// The relative difference between the last price and upper Bollinger band if requested,
+    /// `Some(None)` if there are too few bars.
+    pub pct_from_upper: Option<Option<f64>>,
+    /// The comparison with the benchmark if requested, `Some(None)` if there are too few
+    /// common bars.
+    pub benchmark: Option<Option<BenchmarkComparison>>,
+}
+
+impl Report {
+    ///
+    /// Calculate all signals for a series of closing prices. The period starts with the
+    /// first price.
+    ///
+    /// # Returns
+    ///
+    /// The report or `None` if the series is empty.
+    ///
+    pub async fn from_closes(symbol: &str, closes: &TimeSeries) -> Option<Report> {
+        let (period_start, _) = closes.first()?;
+        // min/max of the period. unwrap() because those are Option types
+        let (_, period_max) = MaxPrice.calculate(closes).await.unwrap();
+        let (_, period_min) = MinPrice.calculate(closes).await.unwrap();
+        let (_, last_price) = closes.last().unwrap();
+        let (_, pct_change) = PriceDifference
+            .calculate(closes)
+            .await
+            .unwrap_or((0.0, 0.0));
+        let sma = WindowedSMA {
+            window_size: SMA_WINDOW,
+        }
+        .calculate(closes)
+        .await
+        .unwrap_or_default();
+
+        Some(Report {
+            period_start,
+            symbol: symbol.to_string(),
+            last_price,
+            pct_change,
+            period_min,
+            period_max,
+            volatility: None,
+            sma: sma.last().map_or(0.0, |(_, sma)| sma),
+            ema: None,
+            rsi: None,
+            pct_from_upper: None,
+            benchmark: None,
+        })
+    }
+
+    ///
+    /// Calculate all signals for the closing prices of `bars` on `options.basis`,
+    /// see [`Report::from_closes`], and the optional columns of `options`. The closing prices
+    /// of `options.benchmark` are `benchmark`, `None` if they couldn't be fetched.
+    ///
+    pub async fn from_bars(
+        symbol: &str,
+        bars: &[Bar],
+        benchmark: Option<&TimeSeries>,
+        options: &ReportOptions,
+    ) -> Option<Report> {
+        let closes = Bar::closes(bars, options.basis);
+        let mut report = Report::from_closes(symbol, &closes).await?;
+        if let Some(window) = options.volatility_window {
+            let volatility = RollingVolatility {
+                window,
+                periods_per_year: options.periods_per_year,
+            };
+            let volatility = volatility.calculate(&closes).await;
+            report.volatility = Some(volatility.and_then(|v| v.last()).map(|(_, v)| v));
+        }
+        if let Some(span) = options.ema_span {
+            let ema = ExponentialMA::with_span(span).calculate(&closes).await;
+            report.ema = Some(ema.and_then(|ema| ema.last()).map(|(_, ema)| ema));
+        }
+        if let Some(period) = options.rsi_period {
+            let rsi = Rsi { period }.calculate(&closes).await;
+            report.rsi = Some(rsi.and_then(|rsi| rsi.last()).map(|(_, rsi)| rsi));
+        }
+        if let Some(bands) = options.bollinger {
+            let bands = bands.calculate(&closes).await.unwrap_or_default();
+            report.pct_from_upper = Some(bands.upper.last().map(|(_, upper)| {
+                let upper = if upper == 0.0 { 1.0 } else { upper };
+                (report.last_price - upper) / upper
+            }));
+        }
+        if options.benchmark.is_some() {
+            report.benchmark = match benchmark {
+                Some(benchmark) => {
+                    let (closes, benchmark) =
+                        closes.align_by(benchmark, |t| options.interval.alignment_key(t));
+                    let stats = BenchmarkStats {
+                        benchmark,
+                        risk_free_rate: options.risk_free_rate,
+                        periods_per_year: options.periods_per_year,
+                    };
+                    Some(stats.calculate(&closes).await)
+                }
+                None => Some(None),
+            };
+        }
+        Some(report)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // a simple way to output CSV data
+        write!(
+            f,
+            "{},{},${:.2},{:.2}%,${:.2},${:.2}",
+            self.period_start.to_rfc3339(),
+            self.symbol,
+            self.last_price,
+            self.pct_change * 100.0,
+            self.period_min,
+            self.period_max,
+        )?;
+        match self.volatility {
+            Some(Some(volatility)) => write!(f, ",{:.2}%", volatility * 100.0)?,
+            // not enough bars
+            Some(None) => write!(f, ",")?,
+            None => {}
+        }
+        write!(f, ",${:.2}", self.sma)?;
+        match self.ema {
+            Some(Some(ema)) => write!(f, ",${:.2}", ema)?,
+            Some(None) => write!(f, ",")?,
+            None => {}
+        }
+        match self.rsi {
+            Some(Some(rsi)) => write!(f, ",{:.2}", rsi)?,
+            Some(None) => write!(f, ",")?,
+            None => {}
+        }
+        match self.pct_from_upper {
+            Some(Some(pct)) => write!(f, ",{:.2}%", pct * 100.0)?,
+            Some(None) => write!(f, ",")?,
+            None => {}
+        }
+        match &self.benchmark {
+            Some(Some(c)) => {
+                let value = |v: Option<f64>, scale: f64, unit: &str| {
+                    v.map_or(String::new(), |v| format!("{:.2}{}", v * scale, unit))
+                };
+                write!(
+                    f,
+                    ",{},{},{},{:.2}%",
+                    value(c.beta, 1.0, ""),
+                    value(c.alpha, 100.0, "%"),
+                    value(c.correlation, 1.0, ""),
+                    c.excess_return * 100.0
+                )?
+            }
+            Some(None) => write!(f, ",,,,")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+///
+/// A row of [`run_performance`]: the risk-adjusted returns of a symbol over a period.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct PerformanceReport {
+    pub period_start: DateTime<Utc>,
+    pub symbol: String,
+    pub performance: Performance,
+}
+
+impl PerformanceReport {
+    ///
+    /// Calculate the [`PerformanceStats`] of a series of closing prices.
+    ///
+    /// # Returns
+    ///
+    /// The report or `None` if there are fewer than two (positive) prices.
+    ///
+    pub async fn from_closes(
+        symbol: &str,
+        closes: &TimeSeries,
+        stats: &PerformanceStats,
+    ) -> Option<PerformanceReport> {
+        let (period_start, _) = closes.first()?;
+        Some(PerformanceReport {
+            period_start,
+            symbol: symbol.to_string(),
+            performance: stats.calculate(closes).await?,
+        })
+    }
+}
+
+impl fmt::Display for PerformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ratio = |r: Option<f64>| r.map_or(String::new(), |r| format!("{:.2}", r));
+        let p = &self.performance;
+        write!(
+            f,
+            "{},{},{:.2}%,{},{},{},{:.2}%",
+            self.period_start.to_rfc3339(),
+            self.symbol,
+            p.cagr * 100.0,
+            ratio(p.sharpe),
+            ratio(p.sortino),
+            ratio(p.calmar),
+            p.ulcer_index * 100.0
+        )
+    }
+}
+
+///
+/// Settings for [`run`].
+///
+#[derive(Clone, Debug)]
+pub struct ReportOptions {
+    /// The maximum number of symbols that are fetched and processed at the same time.
+    pub max_concurrent: usize,
+    /// Output the rows sorted by symbol instead of in input order.
+    pub sorted: bool,
+    /// How failed fetches are retried.
+    pub retry: RetryPolicy,
+    /// The closing prices the signals are calculated on.
+    pub basis: PriceBasis,
+    /// The period of a bar.
+    pub interval: Interval,
+    /// Add a column with the annualized volatility over this many bars, after min and max.
+    pub volatility_window: Option<usize>,
+    /// Add a column with the exponential moving average over this many bars.
+    pub ema_span: Option<usize>,
+    /// Add a column with the relative strength index over this many bars.
+    pub rsi_period: Option<usize>,
+    /// Add a column with the distance of the price from the upper band of these Bollinger bands.
+    pub bollinger: Option<BollingerBands>,
+    /// The periods of the MACD whose crossovers [`run_crossovers`] reports.
+    pub macd: Macd,
+    /// The number of bars per year all annualized values use, see [`Interval::periods_per_year`].
+    pub periods_per_year: f64,
+    /// The annual risk-free rate of the Sharpe and Sortino ratios of [`run_performance`]
+    /// and the alpha against the benchmark.
+    pub risk_free_rate: f64,
+    /// Add columns comparing each symbol with this one, e.g. an index like `SPY`.
+    pub benchmark: Option<String>,
+    /// How [`run_correlation`] writes its matrices.
+    pub matrix_format: MatrixFormat,
+    /// Let [`run_correlation`] write a matrix for each this many consecutive returns instead
+    /// of one for the whole period.
+    pub correlation_window: Option<usize>,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        ReportOptions {
+            max_concurrent: 8,
+            sorted: false,
+            retry: RetryPolicy::default(),
+            basis: PriceBasis::default(),
+            interval: Interval::default(),
+            volatility_window: None,
+            ema_span: None,
+            rsi_period: None,
+            bollinger: None,
+            macd: Macd::default(),
+            periods_per_year: Interval::default().periods_per_year(),
+            risk_free_rate: 0.0,
+            benchmark: None,
+            matrix_format: MatrixFormat::default(),
+            correlation_window: None,
+        }
+    }
+}
+
+///
+/// Exit code for a run in which some, but not all, symbols failed.
+///
+pub const EXIT_PARTIAL_FAILURE: i32 = 2;
+
+///
+/// Exit code for a run in which all symbols failed.
+///
+pub const EXIT_FAILURE: i32 = 1;
+
+///
+/// Exit code for a run in which all symbols were reported, but the benchmark failed.
+///
+pub const EXIT_BENCHMARK_FAILURE: i32 = 3;
+
+///
+/// The outcome of [`run`]: the symbols that were reported and the errors of those that weren't.
+///
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<FetchError>,
+    /// Why the benchmark couldn't be fetched, if it was requested. It's not one of the symbols.
+    pub benchmark_error: Option<FetchError>,
+}
+
+impl RunSummary {
+    ///
+    /// The process exit code for this run: 0 if all symbols succeeded,
+    /// [`EXIT_PARTIAL_FAILURE`] if only some failed and [`EXIT_FAILURE`] if all of them did.
+    /// If no symbol failed but the benchmark did, [`EXIT_BENCHMARK_FAILURE`].
+    ///
+    pub fn exit_code(&self) -> i32 {
+        match (self.succeeded.len(), self.failed.len()) {
+            (_, 0) if self.benchmark_error.is_some() => EXIT_BENCHMARK_FAILURE,
+            (_, 0) => 0,
+            (0, _) => EXIT_FAILURE,
+            _ => EXIT_PARTIAL_FAILURE,
+        }
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.failed.is_empty() || self.benchmark_error.is_none() {
+            write!(
+                f,
+                "{} of {} symbols failed",
+                self.failed.len(),
+                self.failed.len() + self.succeeded.len()
+            )?;
+            for e in &self.failed {
+                write!(f, "\n  {}: {}", e.symbol, e.kind)?;
+            }
+            if self.benchmark_error.is_some() {
+                writeln!(f)?;
+            }
+        }
+        if let Some(e) = &self.benchmark_error {
+            write!(f, "benchmark {} failed: {}", e.symbol, e.kind)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Fetch the closing prices for each symbol, and the benchmark in one of the
+/// `options.max_concurrent` slots, and write the CSV report to `out`.
+///
+/// # Returns
+///
+/// A summary with the symbols that failed (which are left out of the report) and the
+/// benchmark if it failed (whose columns are left empty), or an io::Error if writing to
+/// `out` failed.
+///
+pub async fn run<W: Write>(
+    out: &mut W,
+    provider: &dyn QuoteProvider,
+    symbols: &[&str],
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    options: &ReportOptions,
+) -> std::io::Result<RunSummary> {
+    writeln!(out, "{}", csv_header(options))?;
+    // fetched once, in the first slot, while the symbols wait for it to compare with it
+    let benchmark_error = Mutex::new(None);
+    let benchmark = async {
+        let benchmark = options.benchmark.as_deref()?;
+        match fetch_bars(
+            provider,
+            &options.retry,
+            benchmark,
+            from,
+            to,
+            options.interval,
+        )
+        .await
+        {
+            Ok(bars) => Some(Arc::new(Bar::closes(&bars, options.basis))),
+            Err(e) => {
+                *benchmark_error.lock().unwrap() = Some(e);
+                None
+            }
+        }
+    }
+    .shared();
+    let lead = options
+        .benchmark
+        .as_ref()
+        .map(|_| benchmark.clone().map(|_| ()));
+    let mut summary = fetch_each_after(
+        lead,
+        symbols,
+        options,
+        |symbol| {
+            let benchmark = benchmark.clone();
+            async move {
+                let bars = fetch_bars(provider, &options.retry, symbol, from, to, options.interval);
+                let (bars, benchmark) = future::join(bars, benchmark).await;
+                let bars = bars?;
+                Ok(Report::from_bars(symbol, &bars, benchmark.as_deref(), options).await)
+            }
+        },
+        |_, report| match report {
+            Some(report) => writeln!(out, "{}", report),
+            None => Ok(()),
+        },
+    )
+    .await?;
+    // without the benchmark the symbols are still reported, with empty columns for it
+    benchmark.await;
+    summary.benchmark_error = benchmark_error.into_inner().unwrap();
+    Ok(summary)
+}
+
+///
+/// Fetch the dividends and splits of each symbol and write them to `out` as CSV,
+/// one row per action.
+///
+/// # Returns
+///
+/// A summary with the symbols that failed, or an io::Error if writing to `out` failed.
+///
+pub async fn run_actions<W: Write>(
+    out: &mut W,
+    provider: &dyn QuoteProvider,
+    symbols: &[&str],
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    options: &ReportOptions,
+) -> std::io::Result<RunSummary> {
+    writeln!(out, "{}", ACTIONS_CSV_HEADER)?;
+    fetch_each(
+        symbols,
+        options,
+        |symbol| fetch_actions(provider, &options.retry, symbol, from, to),
+        |symbol, actions| {
+            for action in actions {
+                writeln!(out, "{},{}", symbol, action)?;
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+///
+/// Fetch the bars of each symbol and write the crossovers of its MACD (`options.macd`) with
+/// the signal line to `out` as CSV, one row per crossover.
+///
+/// # Returns
+///
+/// A summary with the symbols that failed, or an io::Error if writing to `out` failed.
+///
+pub async fn run_crossovers<W: Write>(
+    out: &mut W,
+    provider: &dyn QuoteProvider,
+    symbols: &[&str],
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    options: &ReportOptions,
+) -> std::io::Result<RunSummary> {
+    writeln!(out, "{}", CROSSOVERS_CSV_HEADER)?;
+    fetch_each(
+        symbols,
+        options,
+        |symbol| async move {
+            let bars =
+                fetch_bars(provider, &options.retry, symbol, from, to, options.interval).await?;
+            let macd = options
+                .macd
+                .calculate(&Bar::closes(&bars, options.basis))
+                .await
+                .unwrap_or_default();
+            Ok(macd.crossovers())
+        },
+        |symbol, crossovers| {
+            for (timestamp, crossover) in crossovers {
+                writeln!(out, "{},{},{}", symbol, timestamp.to_rfc3339(), crossover)?;
+            }
+            Ok(())
+        },
+    )
+    .await
+}
+
+///
+/// Fetch the bars of each symbol and write its [`PerformanceReport`] with `options.risk_free_rate`
+/// to `out` as CSV, instead of [`run`]'s price columns.
+///
+/// # Returns
+///
+/// A summary with the symbols that failed (which are left out of the report),
+/// or an io::Error if writing to `out` failed.
+///
+pub async fn run_performance<W: Write>(
+    out: &mut W,
+    provider: &dyn QuoteProvider,
+    symbols: &[&str],
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    options: &ReportOptions,
+) -> std::io::Result<RunSummary> {
+    writeln!(out, "{}", PERFORMANCE_CSV_HEADER)?;
+    let stats = &PerformanceStats {
+        risk_free_rate: options.risk_free_rate,
+        periods_per_year: options.periods_per_year,
+    };
+    fetch_each(
+        symbols,
+        options,
+        |symbol| async move {
+            let bars =
+                fetch_bars(provider, &options.retry, symbol, from, to, options.interval).await?;
+            let closes = Bar::closes(&bars, options.basis);
+            Ok(PerformanceReport::from_closes(symbol, &closes, stats).await)
+        },
+        // too few bars aren't a failure
+        |_, report| match report {
+            Some(report) => writeln!(out, "{}", report),
+            None => Ok(()),
+        },
+    )
+    .await
+}
+
+///
+/// Fetch the bars of all symbols and write the correlations and covariances of their returns
+/// on the bars they all have (see [`Interval::alignment_key`]) to `out`, in `options.matrix_format`.
+/// Symbols that fail are left out of the matrix.
+///
+/// # Returns
+///
+/// A summary with the symbols that failed, or an io::Error if writing to `out` failed.
+///
+pub async fn run_correlation<W: Write>(
+    out: &mut W,
+    provider: &dyn QuoteProvider,
+    symbols: &[&str],
+    from: &DateTime<Utc>,
+    to: &DateTime<Utc>,
+    options: &ReportOptions,
+) -> std::io::Result<RunSummary> {
+    let (mut fetched, mut series) = (vec![], vec![]);
+    let summary = fetch_each(
+        symbols,
+        options,
+        |symbol| async move {
+            let bars =
+                fetch_bars(provider, &options.retry, symbol, from, to, options.interval).await?;
+            Ok(Bar::closes(&bars, options.basis))
+        },
+        |symbol, closes| {
+            fetched.push(symbol);
+            series.push(closes);
+            Ok(())
+        },
+    )
+    .await?;
+
+    let returns: Vec<TimeSeries> =
+        TimeSeries::align_all_by(&series, |t| options.interval.alignment_key(t))
+            .iter()
+            .map(correlation::returns)
+            .collect();
+    let matrices = match options.correlation_window {
+        Some(window) => CorrelationMatrix::rolling(&fetched, &returns, window),
+        None => vec![CorrelationMatrix::new(&fetched, &returns)],
+    };
+    match options.matrix_format {
+        MatrixFormat::Csv => {
+            writeln!(out, "{}", correlation::csv_header(&fetched))?;
+            for matrix in &matrices {
+                write!(out, "{}", matrix.to_csv())?;
+            }
+        }
+        MatrixFormat::Json => {
+            let json: Vec<_> = matrices.iter().map(CorrelationMatrix::to_json).collect();
+            writeln!(out, "{}", serde_json::Value::from(json))?;
+        }
+    }
+    Ok(summary)
+}
+
+///
+/// Call `fetch` for each symbol and pass the results to `write`. This is how all the
+/// `run` functions fetch their symbols: up to `options.max_concurrent` fetches are in flight
+/// at the same time, but the results are always written in input order (or sorted by symbol
+/// with `options.sorted`). A symbol whose `fetch` fails is not written and doesn't stop the
+/// others.
+///
+/// # Returns
+///
+/// A summary with the symbols that were written and those whose `fetch` failed, or the
+/// first error of `write`, which stops the remaining fetches.
+///
+async fn fetch_each<'s, T, Fut>(
+    symbols: &[&'s str],
+    options: &ReportOptions,
+    fetch: impl Fn(&'s str) -> Fut,
+    write: impl FnMut(&'s str, T) -> std::io::Result<()>,
+) -> std::io::Result<RunSummary>
+where
+    Fut: Future<Output = Result<T, FetchError>>,
+{
+    fetch_each_after(None::<future::Ready<()>>, symbols, options, fetch, write).await
+}
+
+///
+/// Like [`fetch_each`], but run `lead` (e.g. fetching a benchmark) first, in one of the
+/// `options.max_concurrent` slots.
+///
+async fn fetch_each_after<'s, T, Fut>(
+    lead: Option<impl Future<Output = ()>>,
+    symbols: &[&'s str],
+    options: &ReportOptions,
+    fetch: impl Fn(&'s str) -> Fut,
+    mut write: impl FnMut(&'s str, T) -> std::io::Result<()>,
+) -> std::io::Result<RunSummary>
+where
+    Fut: Future<Output = Result<T, FetchError>>,
+{
+    let mut symbols = symbols.to_vec();
+    if options.sorted {
+        symbols.sort_unstable();
+    }
+
+    let lead = stream::iter(lead).map(|lead| lead.map(|_| None).left_future());
+    let fetches = stream::iter(symbols).map(|symbol| {
+        fetch(symbol)
+            .map(move |result| Some((symbol, result)))
+            .right_future()
+    });
+    // buffered() runs the futures concurrently but yields their results in order
+    let mut results = lead.chain(fetches).buffered(options.max_concurrent.max(1));
+    let mut summary = RunSummary::default();
+    while let Some(result) = results.next().await {
+        let (symbol, result) = match result {
+            Some(result) => result,
+            None => continue,
+        };
+        match result {
+            Ok(value) => {
+                write(symbol, value)?;
+                summary.succeeded.push(symbol.to_string());
+            }
+            Err(e) => summary.failed.push(e),
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{ActionKind, CorporateAction};
+    use crate::provider::tests::quote;
+    use crate::provider::{InMemoryProvider, Quote};
+    use crate::series::tests::daily;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_report_from_closes() {
+        assert_eq!(aw!(Report::from_closes("ABC", &daily(&[]))), None);
+
+        let report = aw!(Report::from_closes("ABC", &daily(&[2.0, 1.0, 4.0]))).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "2021-01-04T00:00:00+00:00,ABC,$4.00,100.00%,$1.00,$4.00,$0.00"
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", vec![quote(86400, 2.0), quote(2 * 86400, 3.0)])
+            .with_quotes("EMPTY", vec![]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(3 * 86400, 0));
+        let options = ReportOptions::default();
+        let mut out = Vec::new();
+
+        let summary = aw!(run(&mut out, &provider, &["ABC"], &from, &to, &options)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,30d avg\n\
+             1970-01-02T00:00:00+00:00,ABC,$3.00,50.00%,$2.00,$3.00,$0.00\n"
+        );
+        assert_eq!(summary.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_run_with_failures() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", vec![quote(86400, 2.0), quote(2 * 86400, 3.0)])
+            .with_quotes("EMPTY", vec![]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(3 * 86400, 0));
+        let options = ReportOptions::default();
+        let mut out = Vec::new();
+
+        let summary = aw!(run(
+            &mut out,
+            &provider,
+            &["EMPTY", "ABC", "XYZ"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,30d avg\n\
+             1970-01-02T00:00:00+00:00,ABC,$3.00,50.00%,$2.00,$3.00,$0.00\n"
+        );
+        assert_eq!(summary.succeeded, vec!["ABC"]);
+        assert_eq!(
+            summary.to_string(),
+            "2 of 3 symbols failed\n  EMPTY: no data\n  XYZ: unknown symbol"
+        );
+        assert_eq!(summary.exit_code(), EXIT_PARTIAL_FAILURE);
+
+        let summary = aw!(run(
+            &mut Vec::new(),
+            &provider,
+            &["XYZ"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(summary.exit_code(), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn test_run_raw_basis() {
+        let provider = InMemoryProvider::new().with_quotes(
+            "ABC",
+            vec![
+                Quote {
+                    close: 4.0,
+                    ..quote(86400, 2.0)
+                },
+                Quote {
+                    close: 6.0,
+                    ..quote(2 * 86400, 3.0)
+                },
+            ],
+        );
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(3 * 86400, 0));
+        let options = ReportOptions {
+            basis: PriceBasis::Raw,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        aw!(run(&mut out, &provider, &["ABC"], &from, &to, &options)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,30d avg\n\
+             1970-01-02T00:00:00+00:00,ABC,$6.00,50.00%,$4.00,$6.00,$0.00\n"
+        );
+    }
+
+    #[test]
+    fn test_run_intraday() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", vec![quote(3600, 2.0), quote(2 * 3600, 3.0)]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(3 * 3600, 0));
+        let options = ReportOptions {
+            interval: Interval::OneHour,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        aw!(run(&mut out, &provider, &["ABC"], &from, &to, &options)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,30x1h avg\n\
+             1970-01-01T01:00:00+00:00,ABC,$3.00,50.00%,$2.00,$3.00,$0.00\n"
+        );
+    }
+
+    ///
+    /// Daily quotes with `prices`, one per day from the second day of 1970.
+    ///
+    fn prices(prices: &[f64]) -> Vec<Quote> {
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, p)| quote((i as u64 + 1) * 86400, *p))
+            .collect()
+    }
+
+    #[test]
+    fn test_run_with_signals() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", prices(&[2.0, 4.0, 3.0]))
+            .with_quotes("NEW", prices(&[2.0]));
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(5 * 86400, 0));
+        // the options, and the header, ABC and NEW columns after the max column. The
+        // signal values themselves are tested in the signals module
+        let cases = vec![
+            (
+                ReportOptions {
+                    ema_span: Some(2),
+                    ..Default::default()
+                },
+                "30d avg,2d ema",
+                "$0.00,$3.00",
+                "$0.00,",
+            ),
+            (
+                ReportOptions {
+                    rsi_period: Some(2),
+                    ..Default::default()
+                },
+                "30d avg,2d rsi",
+                "$0.00,66.67",
+                "$0.00,",
+            ),
+            (
+                ReportOptions {
+                    bollinger: Some(BollingerBands { window: 2, k: 2.0 }),
+                    ..Default::default()
+                },
+                "30d avg,% from 2d upper band",
+                "$0.00,-33.33%",
+                "$0.00,",
+            ),
+            (
+                ReportOptions {
+                    volatility_window: Some(2),
+                    ema_span: Some(2),
+                    ..Default::default()
+                },
+                "2d volatility,30d avg,2d ema",
+                "1100.98%,$0.00,$3.00",
+                ",$0.00,",
+            ),
+        ];
+
+        for (options, header, abc, new) in cases {
+            let mut out = Vec::new();
+            aw!(run(
+                &mut out,
+                &provider,
+                &["ABC", "NEW"],
+                &from,
+                &to,
+                &options
+            ))
+            .unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                format!(
+                    "period start,symbol,price,change %,min,max,{}\n\
+                     1970-01-02T00:00:00+00:00,ABC,$3.00,50.00%,$2.00,$4.00,{}\n\
+                     1970-01-02T00:00:00+00:00,NEW,$2.00,0.00%,$2.00,$2.00,{}\n",
+                    header, abc, new
+                )
+            );
+        }
+
+        let options = ReportOptions {
+            interval: Interval::OneHour,
+            ema_span: Some(12),
+            ..Default::default()
+        };
+        assert_eq!(
+            csv_header(&options),
+            "period start,symbol,price,change %,min,max,30x1h avg,12x1h ema"
+        );
+    }
+
+    #[test]
+    fn test_run_actions() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", vec![quote(86400, 2.0)])
+            .with_actions(
+                "ABC",
+                vec![
+                    CorporateAction {
+                        timestamp: Utc.timestamp(2 * 86400, 0),
+                        kind: ActionKind::Dividend { amount: 0.25 },
+                    },
+                    CorporateAction {
+                        timestamp: Utc.timestamp(86400, 0),
+                        kind: ActionKind::Split {
+                            numerator: 2.0,
+                            denominator: 1.0,
+                        },
+                    },
+                ],
+            )
+            .with_quotes("NONE", vec![quote(86400, 2.0)]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(3 * 86400, 0));
+        let mut out = Vec::new();
+
+        let summary = aw!(run_actions(
+            &mut out,
+            &provider,
+            &["ABC", "NONE", "XYZ"],
+            &from,
+            &to,
+            &ReportOptions::default()
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "symbol,date,action,value\n\
+             ABC,1970-01-02T00:00:00+00:00,split,2:1\n\
+             ABC,1970-01-03T00:00:00+00:00,dividend,0.25\n"
+        );
+        assert_eq!(summary.succeeded, vec!["ABC", "NONE"]);
+        assert_eq!(summary.exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    fn test_run_crossovers() {
+        let closes = [4.0, 5.0, 3.0, 4.0, 2.0, 1.0, 2.0, 3.0, 4.0, 3.0, 2.0, 1.0];
+        let provider = InMemoryProvider::new().with_quotes(
+            "ABC",
+            closes
+                .iter()
+                .enumerate()
+                .map(|(i, c)| quote((i as u64 + 1) * 86400, *c))
+                .collect(),
+        );
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(13 * 86400, 0));
+        let options = ReportOptions {
+            macd: Macd {
+                fast: 2,
+                slow: 3,
+                signal: 2,
+            },
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        let summary = aw!(run_crossovers(
+            &mut out,
+            &provider,
+            &["ABC", "XYZ"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "symbol,date,crossover\n\
+             ABC,1970-01-06T00:00:00+00:00,bearish\n\
+             ABC,1970-01-08T00:00:00+00:00,bullish\n\
+             ABC,1970-01-11T00:00:00+00:00,bearish\n"
+        );
+        assert_eq!(summary.exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    fn test_run_performance() {
+        let provider = InMemoryProvider::new()
+            .with_quotes(
+                "ABC",
+                vec![
+                    quote(86400, 100.0),
+                    quote(2 * 86400, 110.0),
+                    quote(3 * 86400, 99.0),
+                    quote(4 * 86400, 118.8),
+                ],
+            )
+            .with_quotes("FLAT", vec![quote(86400, 2.0), quote(2 * 86400, 2.0)])
+            .with_quotes("NEW", vec![quote(86400, 2.0)]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(5 * 86400, 0));
+        let options = ReportOptions {
+            periods_per_year: 3.0,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        let summary = aw!(run_performance(
+            &mut out,
+            &provider,
+            &["ABC", "FLAT", "NEW"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,cagr,sharpe,sortino,calmar,ulcer index\n\
+             1970-01-02T00:00:00+00:00,ABC,18.80%,0.76,2.00,1.88,5.00%\n\
+             1970-01-02T00:00:00+00:00,FLAT,0.00%,,,,0.00%\n"
+        );
+        // too few bars aren't a failure
+        assert_eq!(summary.exit_code(), 0);
+    }
+
+    #[test]
+    fn test_run_with_benchmark() {
+        // ABC's returns are twice those of IDX, at a different time of the day. With a
+        // risk-free return of 10% per bar, that leaves an alpha of 10% per bar
+        let abc = prices(&[50.0, 60.0, 48.0, 67.2])
+            .into_iter()
+            .map(|q| Quote {
+                timestamp: q.timestamp + 3600,
+                ..q
+            })
+            .collect();
+        let provider = InMemoryProvider::new()
+            .with_quotes("IDX", prices(&[100.0, 110.0, 99.0, 118.8]))
+            .with_quotes("ABC", abc)
+            .with_quotes("NEW", vec![quote(86400, 2.0)]);
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(5 * 86400, 0));
+        let options = ReportOptions {
+            benchmark: Some("IDX".to_string()),
+            risk_free_rate: 0.3,
+            periods_per_year: 3.0,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+
+        let summary = aw!(run(
+            &mut out,
+            &provider,
+            &["ABC", "IDX", "NEW"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,30d avg,\
+             beta vs IDX,alpha vs IDX,correlation vs IDX,excess return vs IDX\n\
+             1970-01-02T01:00:00+00:00,ABC,$67.20,34.40%,$48.00,$67.20,$0.00,2.00,30.00%,1.00,15.60%\n\
+             1970-01-02T00:00:00+00:00,IDX,$118.80,18.80%,$99.00,$118.80,$0.00,1.00,0.00%,1.00,0.00%\n\
+             1970-01-02T00:00:00+00:00,NEW,$2.00,0.00%,$2.00,$2.00,$0.00,,,,\n"
+        );
+        assert_eq!(summary.exit_code(), 0);
+
+        let options = ReportOptions {
+            benchmark: Some("XYZ".to_string()),
+            ..options
+        };
+        let mut out = Vec::new();
+        let summary = aw!(run(
+            &mut out,
+            &provider,
+            &["ABC", "NEW"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "period start,symbol,price,change %,min,max,30d avg,\
+             beta vs XYZ,alpha vs XYZ,correlation vs XYZ,excess return vs XYZ\n\
+             1970-01-02T01:00:00+00:00,ABC,$67.20,34.40%,$48.00,$67.20,$0.00,,,,\n\
+             1970-01-02T00:00:00+00:00,NEW,$2.00,0.00%,$2.00,$2.00,$0.00,,,,\n"
+        );
+        assert_eq!(summary.to_string(), "benchmark XYZ failed: unknown symbol");
+        assert_eq!(summary.exit_code(), EXIT_BENCHMARK_FAILURE);
+
+        let mut out = Vec::new();
+        let summary = aw!(run(
+            &mut out,
+            &provider,
+            &["ABC", "DEF"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(
+            summary.to_string(),
+            "1 of 2 symbols failed\n  DEF: unknown symbol\nbenchmark XYZ failed: unknown symbol"
+        );
+        assert_eq!(summary.exit_code(), EXIT_PARTIAL_FAILURE);
+    }
+
+    #[test]
+    fn test_run_correlation() {
+        let provider = InMemoryProvider::new()
+            .with_quotes("ABC", prices(&[100.0, 110.0, 99.0, 118.8, 120.0]))
+            // no bar on the last day
+            .with_quotes("DEF", prices(&[50.0, 60.0, 48.0, 67.2]));
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(6 * 86400, 0));
+        let mut out = Vec::new();
+
+        let summary = aw!(run_correlation(
+            &mut out,
+            &provider,
+            &["DEF", "XYZ", "ABC"],
+            &from,
+            &to,
+            &ReportOptions::default()
+        ))
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "date,statistic,symbol,DEF,ABC\n\
+             1970-01-05T00:00:00+00:00,correlation,DEF,1.000000,1.000000\n\
+             1970-01-05T00:00:00+00:00,correlation,ABC,1.000000,1.000000\n\
+             1970-01-05T00:00:00+00:00,covariance,DEF,0.093333,0.046667\n\
+             1970-01-05T00:00:00+00:00,covariance,ABC,0.046667,0.023333\n"
+        );
+        assert_eq!(summary.exit_code(), EXIT_PARTIAL_FAILURE);
+
+        let options = ReportOptions {
+            matrix_format: MatrixFormat::Json,
+            correlation_window: Some(2),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        aw!(run_correlation(
+            &mut out,
+            &provider,
+            &["ABC", "DEF"],
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let ends: Vec<_> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["end"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            ends,
+            vec!["1970-01-04T00:00:00+00:00", "1970-01-05T00:00:00+00:00"]
+        );
+        assert_eq!(json[0]["symbols"], serde_json::json!(["ABC", "DEF"]));
+        assert_eq!(json[0]["observations"], 2);
+    }
+
+    ///
+    /// Waits before returning the quotes of the wrapped provider and counts how many
+    /// fetches are in flight at most.
+    ///
+    const SLOW_DELAY: Duration = Duration::from_millis(50);
+
+    #[derive(Default)]
+    struct SlowProvider {
+        inner: InMemoryProvider,
+        in_flight: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl QuoteProvider for SlowProvider {
+        async fn history(
+            &self,
+            symbol: &str,
+            start: &DateTime<Utc>,
+            end: &DateTime<Utc>,
+            interval: Interval,
+        ) -> Result<Vec<Quote>, FetchError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(in_flight, Ordering::SeqCst);
+            async_std::task::sleep(SLOW_DELAY).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.inner.history(symbol, start, end, interval).await
+        }
+    }
+
+    #[test]
+    fn test_run_concurrently() {
+        let symbols = ["F", "E", "D", "C", "B", "A"];
+        let inner = symbols
+            .iter()
+            .enumerate()
+            .fold(InMemoryProvider::new(), |provider, (i, symbol)| {
+                provider.with_quotes(symbol, vec![quote(86400, i as f64)])
+            });
+        let (from, to) = (Utc.timestamp(0, 0), Utc.timestamp(2 * 86400, 0));
+        let counted_run = |max_concurrent: usize, sorted: bool| {
+            let provider = SlowProvider {
+                inner: inner.clone(),
+                ..Default::default()
+            };
+            let options = ReportOptions {
+                max_concurrent,
+                sorted,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            let started = std::time::Instant::now();
+            aw!(run(&mut out, &provider, &symbols, &from, &to, &options)).unwrap();
+            let elapsed = started.elapsed();
+            let rows: Vec<String> = String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .skip(1)
+                .map(|row| row.split(',').nth(1).unwrap().to_string())
+                .collect();
+            (provider.peak.load(Ordering::SeqCst), rows, elapsed)
+        };
+
+        let (peak, rows, elapsed) = counted_run(1, false);
+        assert_eq!(peak, 1);
+        assert_eq!(rows, symbols);
+        assert!(elapsed >= SLOW_DELAY * 6);
+
+        let (peak, rows, elapsed) = counted_run(6, false);
+        assert_eq!(peak, 6);
+        assert_eq!(rows, symbols);
+        assert!(elapsed < SLOW_DELAY * 3);
+
+        let (peak, rows, _) = counted_run(3, true);
+        assert_eq!(peak, 3);
+        assert_eq!(rows, vec!["A", "B", "C", "D", "E", "F"]);
+
+        // the benchmark takes one of the slots
+        let provider = SlowProvider {
+            inner,
+            ..Default::default()
+        };
+        let options = ReportOptions {
+            max_concurrent: 2,
+            benchmark: Some("A".to_string()),
+            ..Default::default()
+        };
+        aw!(run(
+            &mut Vec::new(),
+            &provider,
+            &symbols,
+            &from,
+            &to,
+            &options
+        ))
+        .unwrap();
+        assert_eq!(provider.peak.load(Ordering::SeqCst), 2);
+    }
+}
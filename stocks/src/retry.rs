@@ -0,0 +1,215 @@
+use crate::error::{FetchError, FetchErrorKind};
+use std::future::Future;
+use std::time::Duration;
+
+///
+/// The error kinds (see [`FetchErrorKind::name`]) that are retried by default.
+///
+pub const DEFAULT_RETRYABLE: &[&str] = &["transport", "rate-limited", "http"];
+
+///
+/// When and how often a failed fetch is tried again.
+///
+/// The n-th retry waits `base_delay * 2^(n-1)`, capped at `max_delay`. With a `jitter`
+/// of e.g. 0.5, that delay is randomly shortened by up to 50% so that concurrent
+/// fetches don't retry in lockstep. A rate limited fetch waits as long as the
+/// provider asks for (Retry-After) instead, but gives up if that exceeds `max_delay`.
+///
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first one. 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// The fraction (0.0 to 1.0) of each delay that is randomized.
+    pub jitter: f64,
+    /// Names of the error kinds that are retried.
+    pub retryable: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            retryable: DEFAULT_RETRYABLE.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    ///
+    /// A policy that never retries.
+    ///
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_retryable(&self, kind: &FetchErrorKind) -> bool {
+        self.retryable.iter().any(|name| name == kind.name())
+    }
+
+    ///
+    /// The delay before retry number `retry` (starting at 1) after an error of `kind`.
+    /// `random` is a number from 0.0 to 1.0 that determines the jitter.
+    ///
+    /// # Returns
+    ///
+    /// The delay or `None` if the error shouldn't be retried.
+    ///
+    pub fn delay(&self, retry: u32, kind: &FetchErrorKind, random: f64) -> Option<Duration> {
+        if retry >= self.max_attempts || !self.is_retryable(kind) {
+            return None;
+        }
+        if let FetchErrorKind::RateLimited {
+            retry_after: Some(retry_after),
+        } = kind
+        {
+            return if *retry_after <= self.max_delay {
+                Some(*retry_after)
+            } else {
+                None
+            };
+        }
+        let exponential = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(retry - 1))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0) * random.clamp(0.0, 1.0);
+        Some(exponential.mul_f64(1.0 - jitter))
+    }
+
+    ///
+    /// Call `fetch` until it succeeds, fails with an error that isn't retryable,
+    /// or `max_attempts` is reached.
+    ///
+    pub async fn run<T, F, Fut>(&self, mut fetch: F) -> Result<T, FetchError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let mut retry = 1;
+        loop {
+            match fetch().await {
+                Ok(result) => return Ok(result),
+                Err(e) => match self.delay(retry, &e.kind, fastrand::f64()) {
+                    Some(delay) => async_std::task::sleep(delay).await,
+                    None => return Err(e),
+                },
+            }
+            retry += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            jitter: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = policy();
+        let transport = FetchErrorKind::Transport("reset".to_string());
+        let delays: Vec<_> = (1..=5).map(|r| policy.delay(r, &transport, 0.0)).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(200)),
+                Some(Duration::from_millis(400)),
+                Some(Duration::from_millis(800)),
+                None
+            ]
+        );
+        assert_eq!(
+            policy.delay(1, &transport, 1.0),
+            Some(Duration::from_millis(50))
+        );
+        let capped = RetryPolicy {
+            max_attempts: 10,
+            ..policy.clone()
+        };
+        assert_eq!(
+            capped.delay(6, &transport, 0.0),
+            Some(Duration::from_millis(1000))
+        );
+
+        assert_eq!(policy.delay(1, &FetchErrorKind::UnknownSymbol, 0.0), None);
+        let retry_after = |millis| FetchErrorKind::RateLimited {
+            retry_after: Some(Duration::from_millis(millis)),
+        };
+        assert_eq!(
+            policy.delay(1, &retry_after(700), 1.0),
+            Some(Duration::from_millis(700))
+        );
+        assert_eq!(policy.delay(1, &retry_after(5000), 0.0), None);
+    }
+
+    #[test]
+    fn test_run() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            ..Default::default()
+        };
+        // fails with `kind` until the n-th attempt
+        let flaky = |n: u32, kind: fn() -> FetchErrorKind| {
+            let mut attempts = 0;
+            let result = aw!(policy.run(|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < n {
+                        Err(FetchError::new("ABC", kind()))
+                    } else {
+                        Ok(attempt)
+                    }
+                }
+            }));
+            (result.ok(), attempts)
+        };
+        let transport = || FetchErrorKind::Transport("reset".to_string());
+        let rate_limited = || FetchErrorKind::RateLimited {
+            retry_after: Some(Duration::from_millis(2)),
+        };
+
+        assert_eq!(flaky(1, transport), (Some(1), 1));
+        assert_eq!(flaky(4, transport), (Some(4), 4));
+        assert_eq!(flaky(5, transport), (None, 4));
+        assert_eq!(flaky(3, rate_limited), (Some(3), 3));
+        assert_eq!(flaky(3, || FetchErrorKind::UnknownSymbol), (None, 1));
+        assert_eq!(
+            flaky(3, || FetchErrorKind::Http(503)),
+            (Some(3), 3),
+            "http errors are retried by default"
+        );
+        assert_eq!(
+            flaky(3, || FetchErrorKind::Rejected(403)),
+            (None, 1),
+            "rejected requests aren't retried by default"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use chrono::prelude::*;
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+
+///
+/// Values (e.g. closing prices or a signal) with the timestamps they belong to,
+/// sorted by timestamp.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimeSeries {
+    timestamps: Vec<DateTime<Utc>>,
+    values: Vec<f64>,
+}
+
+impl TimeSeries {
+    ///
+    /// A series of `values` at `timestamps`, which have to be sorted.
+    ///
+    /// # Panics
+    ///
+    /// If there aren't as many timestamps as values.
+    ///
+    pub fn new(timestamps: Vec<DateTime<Utc>>, values: Vec<f64>) -> Self {
+        assert_eq!(
+            timestamps.len(),
+            values.len(),
+            "a time series needs one timestamp per value"
+        );
+        TimeSeries { timestamps, values }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn timestamps(&self) -> &[DateTime<Utc>] {
+        &self.timestamps
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn first(&self) -> Option<(DateTime<Utc>, f64)> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<(DateTime<Utc>, f64)> {
+        self.iter().last()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (DateTime<Utc>, f64)> + '_ {
+        self.timestamps
+            .iter()
+            .copied()
+            .zip(self.values.iter().copied())
+    }
+
+    ///
+    /// The part of the series between `start` and `end` (inclusive).
+    ///
+    pub fn between(&self, start: &DateTime<Utc>, end: &DateTime<Utc>) -> TimeSeries {
+        let from = self.timestamps.partition_point(|t| t < start);
+        let to = self.timestamps.partition_point(|t| t <= end).max(from);
+        TimeSeries {
+            timestamps: self.timestamps[from..to].to_vec(),
+            values: self.values[from..to].to_vec(),
+        }
+    }
+
+    ///
+    /// The values of this series and `other` at the same timestamps.
+    ///
+    pub fn align(&self, other: &TimeSeries) -> (TimeSeries, TimeSeries) {
+        self.align_by(other, |t| *t)
+    }
+
+    ///
+    /// The values of this series and `other` whose timestamps have the same `key`, e.g. the date.
+    /// If several timestamps of a series have the same key, the first one is used.
+    ///
+    /// # Returns
+    ///
+    /// Both series with the timestamps of this one.
+    ///
+    pub fn align_by<K, F>(&self, other: &TimeSeries, key: F) -> (TimeSeries, TimeSeries)
+    where
+        K: Ord,
+        F: Fn(&DateTime<Utc>) -> K,
+    {
+        let mut others = BTreeMap::new();
+        for (t, value) in other.iter() {
+            others.entry(key(&t)).or_insert(value);
+        }
+        let (mut left, mut right) = (vec![], vec![]);
+        for (t, value) in self.iter() {
+            // take each key once
+            if let Some(other) = others.remove(&key(&t)) {
+                left.push((t, value));
+                right.push((t, other));
+            }
+        }
+        (left.into_iter().collect(), right.into_iter().collect())
+    }
+
+    ///
+    /// The values of all `series` whose timestamps have the same `key`, see [`TimeSeries::align_by`].
+    ///
+    /// # Returns
+    ///
+    /// One series per element of `series`, all with the timestamps of the first one.
+    ///
+    pub fn align_all_by<K, F>(series: &[TimeSeries], key: F) -> Vec<TimeSeries>
+    where
+        K: Ord,
+        F: Fn(&DateTime<Utc>) -> K,
+    {
+        let first = match series.first() {
+            Some(first) => first,
+            None => return vec![],
+        };
+        let mut common = first.clone();
+        for other in &series[1..] {
+            common = common.align_by(other, &key).0;
+        }
+        series
+            .iter()
+            .map(|other| common.align_by(other, &key).1)
+            .collect()
+    }
+}
+
+impl FromIterator<(DateTime<Utc>, f64)> for TimeSeries {
+    fn from_iter<I: IntoIterator<Item = (DateTime<Utc>, f64)>>(iter: I) -> Self {
+        let (timestamps, values) = iter.into_iter().unzip();
+        TimeSeries { timestamps, values }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    ///
+    /// A daily series of `values`, starting on 2021-01-04.
+    ///
+    pub(crate) fn daily(values: &[f64]) -> TimeSeries {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (day(i), *v))
+            .collect()
+    }
+
+    ///
+    /// The timestamp of the `i`-th value of a [`daily`] series.
+    ///
+    pub(crate) fn day(i: usize) -> DateTime<Utc> {
+        Utc.ymd(2021, 1, 4).and_hms(0, 0, 0) + chrono::Duration::days(i as i64)
+    }
+
+    #[test]
+    fn test_between() {
+        let series = daily(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(series.between(&day(1), &day(2)).values(), &[2.0, 3.0]);
+        assert_eq!(
+            series.between(&(day(0) - chrono::Duration::hours(1)), &day(0)),
+            daily(&[1.0])
+        );
+        assert_eq!(
+            series.between(&day(3), &day(10)).first(),
+            Some((day(3), 4.0))
+        );
+        assert!(series.between(&day(2), &day(1)).is_empty());
+        assert!(series.between(&day(5), &day(6)).is_empty());
+    }
+
+    #[test]
+    fn test_align() {
+        let series = daily(&[1.0, 2.0, 3.0, 4.0]);
+        let other: TimeSeries = vec![(day(1), 20.0), (day(3), 40.0), (day(5), 60.0)]
+            .into_iter()
+            .collect();
+        let (left, right) = series.align(&other);
+        assert_eq!(left.values(), &[2.0, 4.0]);
+        assert_eq!(right.values(), &[20.0, 40.0]);
+        assert_eq!(right.timestamps(), &[day(1), day(3)]);
+
+        // e.g. bars of different exchanges
+        let later: TimeSeries = other
+            .iter()
+            .map(|(t, v)| (t + chrono::Duration::hours(14), v))
+            .collect();
+        assert!(series.align(&later).0.is_empty());
+        let (left, right) = series.align_by(&later, |t| t.date());
+        assert_eq!(left.values(), &[2.0, 4.0]);
+        assert_eq!(right.timestamps(), &[day(1), day(3)]);
+    }
+
+    #[test]
+    fn test_align_all_by() {
+        let a = daily(&[1.0, 2.0, 3.0, 4.0]);
+        let b: TimeSeries = vec![(day(1), 20.0), (day(2), 30.0), (day(3), 40.0)]
+            .into_iter()
+            .collect();
+        let c: TimeSeries = vec![(day(0), 100.0), (day(2), 300.0), (day(3), 400.0)]
+            .into_iter()
+            .collect();
+        let aligned = TimeSeries::align_all_by(&[a, b, c], |t| *t);
+        assert_eq!(aligned[0].values(), &[3.0, 4.0]);
+        assert_eq!(aligned[1].values(), &[30.0, 40.0]);
+        assert_eq!(aligned[2].values(), &[300.0, 400.0]);
+        assert_eq!(aligned[2].timestamps(), &[day(2), day(3)]);
+        assert!(TimeSeries::align_all_by(&[], |t| *t).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_mismatched() {
+        TimeSeries::new(vec![day(0)], vec![]);
+    }
+}
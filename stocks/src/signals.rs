@@ -0,0 +1,1260 @@
+use crate::series::TimeSeries;
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+///
+/// A trait to provide a common interface for all signal calculations.
+///
+#[async_trait]
+pub trait StockSignal {
+    ///
+    /// The signal's data type.
+    /// Associated type for trait:
+    /// https://doc.rust-lang.org/book/ch19-03-advanced-traits.html#specifying-placeholder-types-in-trait-definitions-with-associated-types
+    ///
+    type SignalType;
+
+    ///
+    /// Calculate the signal on the provided series.
+    ///
+    /// # Returns
+    ///
+    /// The signal (using the provided type) or `None` on error/invalid data.
+    ///
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType>;
+}
+
+///
+/// Absolute and relative change between the first and last price of a series.
+///
+pub struct PriceDifference;
+
+#[async_trait]
+impl StockSignal for PriceDifference {
+    type SignalType = (f64, f64);
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        price_diff(series.values()).await
+    }
+}
+///
+/// The lowest price of a series and when it occurred first.
+///
+pub struct MinPrice;
+#[async_trait]
+impl StockSignal for MinPrice {
+    type SignalType = (DateTime<Utc>, f64);
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        min(series).await
+    }
+}
+
+///
+/// The highest price of a series and when it occurred first.
+///
+pub struct MaxPrice;
+#[async_trait]
+impl StockSignal for MaxPrice {
+    type SignalType = (DateTime<Utc>, f64);
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        max(series).await
+    }
+}
+
+///
+/// A simple moving average over `window_size` elements, i.e. bars of whatever interval
+/// the series has. Each average has the timestamp of the last element of its window.
+///
+pub struct WindowedSMA {
+    pub window_size: usize,
+}
+
+#[async_trait]
+impl StockSignal for WindowedSMA {
+    type SignalType = TimeSeries;
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let sma = n_window_sma(self.window_size, series.values())?;
+        Some(
+            series
+                .timestamps()
+                .iter()
+                .skip(self.window_size - 1)
+                .copied()
+                .zip(sma)
+                .collect(),
+        )
+    }
+}
+
+///
+/// An exponential moving average with the smoothing factor `alpha`. The first average
+/// is the simple moving average of the first `span` elements (the warm-up), every further
+/// one is `alpha * price + (1 - alpha) * previous`. Like [`WindowedSMA`], each average has
+/// the timestamp of its last element.
+///
+pub struct ExponentialMA {
+    pub span: usize,
+    pub alpha: f64,
+}
+
+impl ExponentialMA {
+    ///
+    /// The usual EMA over `span` elements, i.e. `alpha = 2 / (span + 1)`.
+    ///
+    pub fn with_span(span: usize) -> Self {
+        ExponentialMA {
+            span,
+            alpha: 2.0 / (span as f64 + 1.0),
+        }
+    }
+
+    ///
+    /// An EMA with the smoothing factor `alpha` (0.0 < alpha <= 1.0), warmed up over
+    /// the span it corresponds to.
+    ///
+    pub fn with_alpha(alpha: f64) -> Self {
+        ExponentialMA {
+            span: (2.0 / alpha - 1.0).round().max(1.0) as usize,
+            alpha,
+        }
+    }
+}
+
+#[async_trait]
+impl StockSignal for ExponentialMA {
+    type SignalType = TimeSeries;
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let ema = n_window_ema(self.span, self.alpha, series.values())?;
+        Some(
+            series
+                .timestamps()
+                .iter()
+                .skip(self.span - 1)
+                .copied()
+                .zip(ema)
+                .collect(),
+        )
+    }
+}
+
+///
+/// The relative strength index over `period` price changes with Wilder's smoothing,
+/// between 0.0 (only losses) and 100.0 (only gains). A series without any change has an RSI of 50.0.
+/// Each value has the timestamp of the last price it covers, so the first one is that of
+/// the element at `period`.
+///
+pub struct Rsi {
+    pub period: usize,
+}
+
+#[async_trait]
+impl StockSignal for Rsi {
+    type SignalType = TimeSeries;
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let rsi = n_period_rsi(self.period, series.values())?;
+        Some(
+            series
+                .timestamps()
+                .iter()
+                .skip(self.period)
+                .copied()
+                .zip(rsi)
+                .collect(),
+        )
+    }
+}
+
+///
+/// Moving average convergence/divergence: the difference between the exponential moving
+/// averages over `fast` and `slow` bars (the MACD line), its exponential moving average over
+/// `signal` bars (the signal line) and the difference between those two (the histogram).
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Macd {
+    pub fast: usize,
+    pub slow: usize,
+    pub signal: usize,
+}
+
+impl Default for Macd {
+    ///
+    /// The common 12/26/9 setting.
+    ///
+    fn default() -> Self {
+        Macd {
+            fast: 12,
+            slow: 26,
+            signal: 9,
+        }
+    }
+}
+
+impl FromStr for Macd {
+    type Err = String;
+
+    ///
+    /// Parse `fast,slow,signal`, e.g. `12,26,9`.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid MACD periods '{}', expected fast,slow,signal", s);
+        let periods = s
+            .split(',')
+            .map(|p| p.trim().parse::<usize>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        match periods.as_slice() {
+            [fast, slow, signal] if 0 < *fast && fast < slow && *signal > 0 => Ok(Macd {
+                fast: *fast,
+                slow: *slow,
+                signal: *signal,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Macd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.fast, self.slow, self.signal)
+    }
+}
+
+///
+/// The lines of a [`Macd`]. The MACD line starts with the `slow`th bar, the signal line
+/// and the histogram `signal - 1` bars later.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MacdSeries {
+    pub macd: TimeSeries,
+    pub signal: TimeSeries,
+    pub histogram: TimeSeries,
+}
+
+///
+/// The direction in which the MACD line crossed the signal line.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Crossover {
+    /// The MACD line rose above the signal line.
+    Bullish,
+    /// The MACD line fell below the signal line.
+    Bearish,
+}
+
+impl fmt::Display for Crossover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crossover::Bullish => f.write_str("bullish"),
+            Crossover::Bearish => f.write_str("bearish"),
+        }
+    }
+}
+
+impl MacdSeries {
+    ///
+    /// The bars on which the MACD line crossed the signal line, i.e. the histogram changed
+    /// its sign. Touching the signal line (a histogram of 0.0) without crossing it isn't a crossover.
+    ///
+    pub fn crossovers(&self) -> Vec<(DateTime<Utc>, Crossover)> {
+        let mut above = None;
+        let mut crossovers = vec![];
+        for (t, h) in self.histogram.iter().filter(|(_, h)| *h != 0.0) {
+            match (above, h > 0.0) {
+                (Some(false), true) => crossovers.push((t, Crossover::Bullish)),
+                (Some(true), false) => crossovers.push((t, Crossover::Bearish)),
+                _ => {}
+            }
+            above = Some(h > 0.0);
+        }
+        crossovers
+    }
+}
+
+#[async_trait]
+impl StockSignal for Macd {
+    type SignalType = MacdSeries;
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        if self.fast == 0 || self.fast >= self.slow || self.signal == 0 {
+            return None;
+        }
+        let ema = |n: usize, values: &[f64]| n_window_ema(n, 2.0 / (n as f64 + 1.0), values);
+        let fast = ema(self.fast, series.values())?;
+        let slow = ema(self.slow, series.values())?;
+        // the fast average starts `slow - fast` bars earlier
+        let macd: Vec<f64> = fast
+            .iter()
+            .skip(self.slow - self.fast)
+            .zip(&slow)
+            .map(|(fast, slow)| fast - slow)
+            .collect();
+        let signal = ema(self.signal, &macd).unwrap_or_default();
+        let histogram: Vec<f64> = macd
+            .iter()
+            .skip(self.signal - 1)
+            .zip(&signal)
+            .map(|(macd, signal)| macd - signal)
+            .collect();
+
+        let timestamps = |skip: usize| series.timestamps().iter().skip(skip).copied();
+        let signal_start = self.slow - 1 + self.signal - 1;
+        Some(MacdSeries {
+            macd: timestamps(self.slow - 1).zip(macd).collect(),
+            signal: timestamps(signal_start).zip(signal).collect(),
+            histogram: timestamps(signal_start).zip(histogram).collect(),
+        })
+    }
+}
+
+///
+/// Bollinger bands: the simple moving average over `window` elements (the middle band) and
+/// `k` standard deviations of the window above (the upper band) and below it (the lower band).
+/// Like [`WindowedSMA`], each value has the timestamp of the last element of its window.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BollingerBands {
+    pub window: usize,
+    pub k: f64,
+}
+
+impl Default for BollingerBands {
+    ///
+    /// The common setting of 20 elements and 2 standard deviations.
+    ///
+    fn default() -> Self {
+        BollingerBands { window: 20, k: 2.0 }
+    }
+}
+
+impl FromStr for BollingerBands {
+    type Err = String;
+
+    ///
+    /// Parse `window,k`, e.g. `20,2`.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid Bollinger bands '{}', expected window,k", s);
+        let mut parts = s.split(',').map(str::trim);
+        let window = parts.next().and_then(|w| w.parse::<usize>().ok());
+        let k = parts.next().and_then(|k| k.parse::<f64>().ok());
+        match (window, k, parts.next()) {
+            (Some(window), Some(k), None) if window > 1 && k > 0.0 => {
+                Ok(BollingerBands { window, k })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+///
+/// The bands of [`BollingerBands`] and %B, the position of the price between the lower (0.0)
+/// and the upper band (1.0). %B is 0.5 if the bands are the same, i.e. the window is flat.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BollingerSeries {
+    pub middle: TimeSeries,
+    pub upper: TimeSeries,
+    pub lower: TimeSeries,
+    pub percent_b: TimeSeries,
+}
+
+#[async_trait]
+impl StockSignal for BollingerBands {
+    type SignalType = BollingerSeries;
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let bands = n_window_bands(self.window, self.k, series.values())?;
+        let timestamps = series.timestamps().iter().skip(self.window - 1).copied();
+        let prices = series.values().iter().skip(self.window - 1);
+        let (mut middle, mut upper, mut lower, mut percent_b) = (vec![], vec![], vec![], vec![]);
+        for ((t, price), (m, u, l)) in timestamps.zip(prices).zip(bands) {
+            middle.push((t, m));
+            upper.push((t, u));
+            lower.push((t, l));
+            percent_b.push((t, if u > l { (price - l) / (u - l) } else { 0.5 }));
+        }
+        Some(BollingerSeries {
+            middle: middle.into_iter().collect(),
+            upper: upper.into_iter().collect(),
+            lower: lower.into_iter().collect(),
+            percent_b: percent_b.into_iter().collect(),
+        })
+    }
+}
+
+///
+/// The volatility: the (sample) standard deviation of the log returns of the last `window`
+/// price changes, annualized with `periods_per_year` (e.g. 252 for daily bars, see
+/// [`Interval::periods_per_year`](crate::Interval::periods_per_year)). Each value has the
+/// timestamp of the last price it covers, so the first one is that of the element at `window`.
+///
+pub struct RollingVolatility {
+    pub window: usize,
+    pub periods_per_year: f64,
+}
+
+#[async_trait]
+impl StockSignal for RollingVolatility {
+    type SignalType = TimeSeries;
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let volatility = n_window_volatility(self.window, series.values())?;
+        let annualize = self.periods_per_year.sqrt();
+        Some(
+            series
+                .timestamps()
+                .iter()
+                .skip(self.window)
+                .copied()
+                .zip(volatility.into_iter().map(|v| v * annualize))
+                .collect(),
+        )
+    }
+}
+
+///
+/// The largest loss from a peak to a later trough of a series and how long it took to recover.
+///
+pub struct MaxDrawdown;
+
+///
+/// The result of [`MaxDrawdown`].
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Drawdown {
+    /// The largest relative loss from a peak, e.g. 0.25 for a drop of 25%.
+    /// 0.0 for a series that never fell, with the peak and trough on its first element.
+    pub max_drawdown: f64,
+    /// When the peak before the largest loss occurred (first).
+    pub peak: DateTime<Utc>,
+    /// When the lowest price after that peak occurred (first).
+    pub trough: DateTime<Utc>,
+    /// When the price got back to the peak, `None` if it didn't within the series.
+    pub recovery: Option<DateTime<Utc>>,
+    /// The longest time from any peak until the price got back to it (or the end of the
+    /// series).
+    pub longest_underwater: chrono::Duration,
+}
+
+#[async_trait]
+impl StockSignal for MaxDrawdown {
+    type SignalType = Drawdown;
+
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        drawdown(series).await
+    }
+}
+
+///
+/// Risk-adjusted return metrics of a price series with `periods_per_year` elements per year
+/// (e.g. 252 for daily bars, see [`Interval::periods_per_year`](crate::Interval::periods_per_year))
+/// and an annual `risk_free_rate` (e.g. 0.02 for 2%).
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PerformanceStats {
+    pub risk_free_rate: f64,
+    pub periods_per_year: f64,
+}
+
+impl Default for PerformanceStats {
+    ///
+    /// Daily bars without a risk-free return.
+    ///
+    fn default() -> Self {
+        PerformanceStats {
+            risk_free_rate: 0.0,
+            periods_per_year: 252.0,
+        }
+    }
+}
+
+///
+/// The result of [`PerformanceStats`]. Ratios are annualized and `None` if they are undefined
+/// because their denominator is 0.0, e.g. the Sharpe ratio of a series without any change.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Performance {
+    /// The compound annual growth rate, e.g. 0.1 for 10% per year.
+    pub cagr: f64,
+    /// The mean excess return over its standard deviation.
+    pub sharpe: Option<f64>,
+    /// The mean excess return over the downside deviation (of the returns below the risk-free rate).
+    pub sortino: Option<f64>,
+    /// The CAGR over the maximum drawdown, see [`MaxDrawdown`].
+    pub calmar: Option<f64>,
+    /// The root mean square of the drawdowns from the running peak, e.g. 0.05 for 5%.
+    pub ulcer_index: f64,
+}
+
+#[async_trait]
+impl StockSignal for PerformanceStats {
+    type SignalType = Performance;
+
+    ///
+    /// Needs at least two prices, all of them positive.
+    ///
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let prices = series.values();
+        if prices.len() < 2 || prices.iter().any(|p| *p <= 0.0) || self.periods_per_year <= 0.0 {
+            return None;
+        }
+        let returns: Vec<f64> = prices.windows(2).map(|w| w[1] / w[0] - 1.0).collect();
+        let n = returns.len() as f64;
+        let years = n / self.periods_per_year;
+        let cagr = (prices[prices.len() - 1] / prices[0]).powf(1.0 / years) - 1.0;
+
+        let excess: Vec<f64> = returns
+            .iter()
+            .map(|r| r - self.risk_free_rate / self.periods_per_year)
+            .collect();
+        let mean = excess.iter().sum::<f64>() / n;
+        let ratio = |deviation: f64| match deviation {
+            d if d > 0.0 => Some(mean / d * self.periods_per_year.sqrt()),
+            _ => None,
+        };
+        let std_dev = match returns.len() {
+            1 => 0.0,
+            _ => {
+                let mean = returns.iter().sum::<f64>() / n;
+                (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+            }
+        };
+        let downside = (excess.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / n).sqrt();
+
+        let max_drawdown = drawdown(series).await?.max_drawdown;
+        let mut peak = prices[0];
+        let squared_drawdowns = prices.iter().map(|p| {
+            peak = peak.max(*p);
+            ((p - peak) / peak).powi(2)
+        });
+        let ulcer_index = (squared_drawdowns.sum::<f64>() / prices.len() as f64).sqrt();
+
+        Some(Performance {
+            cagr,
+            sharpe: ratio(std_dev),
+            sortino: ratio(downside),
+            calmar: match max_drawdown {
+                d if d > 0.0 => Some(cagr / d),
+                _ => None,
+            },
+            ulcer_index,
+        })
+    }
+}
+
+///
+/// Compares a price series with the prices of a `benchmark` (e.g. an index) at the same
+/// timestamps, see [`TimeSeries::align`]. `risk_free_rate` and `periods_per_year` are used
+/// like in [`PerformanceStats`].
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkStats {
+    pub benchmark: TimeSeries,
+    pub risk_free_rate: f64,
+    pub periods_per_year: f64,
+}
+
+///
+/// The result of [`BenchmarkStats`], calculated on the returns at the timestamps both series
+/// have. Values are `None` if they are undefined, e.g. the beta against a flat benchmark.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkComparison {
+    /// How much the series moves with the benchmark, `cov(series, benchmark) / var(benchmark)`.
+    pub beta: Option<f64>,
+    /// The annualized excess return beyond what the beta explains (Jensen's alpha).
+    pub alpha: Option<f64>,
+    /// The correlation of the returns, between -1.0 and 1.0.
+    pub correlation: Option<f64>,
+    /// The total return of the series minus that of the benchmark, e.g. 0.05 for 5 percentage points.
+    pub excess_return: f64,
+}
+
+#[async_trait]
+impl StockSignal for BenchmarkStats {
+    type SignalType = BenchmarkComparison;
+
+    ///
+    /// Needs at least two common timestamps with positive prices.
+    ///
+    async fn calculate(&self, series: &TimeSeries) -> Option<Self::SignalType> {
+        let (series, benchmark) = series.align(&self.benchmark);
+        let (prices, index) = (series.values(), benchmark.values());
+        if prices.len() < 2 || prices.iter().chain(index).any(|p| *p <= 0.0) {
+            return None;
+        }
+        let returns = |p: &[f64]| -> Vec<f64> { p.windows(2).map(|w| w[1] / w[0] - 1.0).collect() };
+        let (r, b) = (returns(prices), returns(index));
+        let n = r.len() as f64;
+        let mean = |x: &[f64]| x.iter().sum::<f64>() / n;
+        let (mean_r, mean_b) = (mean(&r), mean(&b));
+        let covariance = |x: &[f64], mean_x: f64, y: &[f64], mean_y: f64| {
+            x.iter()
+                .zip(y)
+                .map(|(x, y)| (x - mean_x) * (y - mean_y))
+                .sum::<f64>()
+        };
+        let cov = covariance(&r, mean_r, &b, mean_b);
+        let (var_r, var_b) = (
+            covariance(&r, mean_r, &r, mean_r),
+            covariance(&b, mean_b, &b, mean_b),
+        );
+
+        let beta = match var_b {
+            v if v > 0.0 => Some(cov / v),
+            _ => None,
+        };
+        let risk_free = self.risk_free_rate / self.periods_per_year;
+        let total = |p: &[f64]| p[p.len() - 1] / p[0] - 1.0;
+        Some(BenchmarkComparison {
+            beta,
+            alpha: beta.map(|beta| {
+                ((mean_r - risk_free) - beta * (mean_b - risk_free)) * self.periods_per_year
+            }),
+            correlation: match var_r * var_b {
+                v if v > 0.0 => Some(cov / v.sqrt()),
+                _ => None,
+            },
+            excess_return: total(prices) - total(index),
+        })
+    }
+}
+
+///
+/// Calculates the absolute and relative difference between the beginning and ending of an f64 series.
+// The relative difference is relative to the beginning.
+///
+/// # Returns
+///
+/// A tuple `(absolute, relative)` difference.
+///
+async fn price_diff(a: &[f64]) -> Option<(f64, f64)> {
+    if !a.is_empty() {
+        // unwrap is safe here even if first == last
+        let (first, last) = (a.first().unwrap(), a.last().unwrap());
+        let abs_diff = last - first;
+        let first = if *first == 0.0 { 1.0 } else { *first };
+        let rel_diff = abs_diff / first;
+        Some((abs_diff, rel_diff))
+    } else {
+        None
+    }
+}
+
+///
+/// Window function to create a simple moving average
+///
+fn n_window_sma(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if !series.is_empty() && n > 1 {
+        Some(
+            series
+                .windows(n)
+                .map(|w| w.iter().sum::<f64>() / w.len() as f64)
+                .collect(),
+        )
+    } else {
+        None
+    }
+}
+
+///
+/// Window function to create the (middle, upper, lower) Bollinger bands with the population
+/// standard deviation of each window
+///
+fn n_window_bands(n: usize, k: f64, series: &[f64]) -> Option<Vec<(f64, f64, f64)>> {
+    let sma = n_window_sma(n, series)?;
+    Some(
+        series
+            .windows(n)
+            .zip(sma)
+            .map(|(w, mean)| {
+                let variance = w.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / n as f64;
+                let width = k * variance.sqrt();
+                (mean, mean + width, mean - width)
+            })
+            .collect(),
+    )
+}
+
+///
+/// Window function to create the standard deviation of log returns over `n` returns, i.e.
+/// `n + 1` prices. Prices must be positive.
+///
+fn n_window_volatility(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if series.is_empty() || n < 2 || series.iter().any(|p| *p <= 0.0) {
+        return None;
+    }
+    let returns: Vec<f64> = series.windows(2).map(|w| (w[1] / w[0]).ln()).collect();
+    Some(
+        returns
+            .windows(n)
+            .map(|w| {
+                let mean = w.iter().sum::<f64>() / n as f64;
+                let variance = w.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+                variance.sqrt()
+            })
+            .collect(),
+    )
+}
+
+///
+/// Exponential moving average seeded with the simple moving average of the first `n` elements
+///
+fn n_window_ema(n: usize, alpha: f64, series: &[f64]) -> Option<Vec<f64>> {
+    if series.is_empty() || n == 0 || !(alpha > 0.0 && alpha <= 1.0) {
+        return None;
+    }
+    if series.len() < n {
+        return Some(vec![]);
+    }
+    let seed = series[..n].iter().sum::<f64>() / n as f64;
+    let rest = series[n..].iter().scan(seed, |ema, price| {
+        *ema = alpha * price + (1.0 - alpha) * *ema;
+        Some(*ema)
+    });
+    Some(std::iter::once(seed).chain(rest).collect())
+}
+
+///
+/// Relative strength index, the average gains and losses are seeded with the mean of the
+/// first `n` changes and smoothed with `(previous * (n - 1) + change) / n`
+///
+fn n_period_rsi(n: usize, series: &[f64]) -> Option<Vec<f64>> {
+    if n == 0 || series.len() <= n {
+        return None;
+    }
+    let changes: Vec<f64> = series.windows(2).map(|w| w[1] - w[0]).collect();
+    let gain = |c: &f64| c.max(0.0);
+    let loss = |c: &f64| (-c).max(0.0);
+    let mut avg_gain = changes[..n].iter().map(gain).sum::<f64>() / n as f64;
+    let mut avg_loss = changes[..n].iter().map(loss).sum::<f64>() / n as f64;
+
+    let rsi = |avg_gain: f64, avg_loss: f64| {
+        if avg_loss == 0.0 {
+            if avg_gain == 0.0 {
+                50.0
+            } else {
+                100.0
+            }
+        } else {
+            100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
+        }
+    };
+    let mut values = vec![rsi(avg_gain, avg_loss)];
+    for change in &changes[n..] {
+        avg_gain = (avg_gain * (n - 1) as f64 + gain(change)) / n as f64;
+        avg_loss = (avg_loss * (n - 1) as f64 + loss(change)) / n as f64;
+        values.push(rsi(avg_gain, avg_loss));
+    }
+    Some(values)
+}
+
+///
+/// Find the largest drawdown and the longest time under water of a series
+///
+async fn drawdown(series: &TimeSeries) -> Option<Drawdown> {
+    let (first, first_price) = series.first()?;
+    let mut result = Drawdown {
+        max_drawdown: 0.0,
+        peak: first,
+        trough: first,
+        recovery: None,
+        longest_underwater: chrono::Duration::zero(),
+    };
+    let (mut peak, mut peak_price) = (first, first_price);
+    let mut max_peak_price = first_price;
+    let mut underwater = false;
+    for (t, price) in series.iter() {
+        // a price equal to the peak only starts a new one after a loss, so a plateau keeps its first
+        if price > peak_price || (price == peak_price && underwater) {
+            if underwater {
+                result.longest_underwater = result.longest_underwater.max(t - peak);
+                underwater = false;
+            }
+            if result.recovery.is_none() && result.max_drawdown > 0.0 && price >= max_peak_price {
+                result.recovery = Some(t);
+            }
+            peak = t;
+            peak_price = price;
+        } else if price < peak_price && peak_price > 0.0 {
+            underwater = true;
+            let drawdown = (peak_price - price) / peak_price;
+            if drawdown > result.max_drawdown {
+                result.max_drawdown = drawdown;
+                result.peak = peak;
+                result.trough = t;
+                result.recovery = None;
+                max_peak_price = peak_price;
+            }
+        }
+    }
+    if underwater {
+        let (last, _) = series.last()?;
+        result.longest_underwater = result.longest_underwater.max(last - peak);
+    }
+    Some(result)
+}
+
+///
+/// Find the (first) maximum in a series
+///
+async fn max(series: &TimeSeries) -> Option<(DateTime<Utc>, f64)> {
+    series.iter().fold(None, |acc, (t, q)| match acc {
+        Some((_, max)) if max >= q => acc,
+        _ => Some((t, q)),
+    })
+}
+
+///
+/// Find the (first) minimum in a series
+///
+async fn min(series: &TimeSeries) -> Option<(DateTime<Utc>, f64)> {
+    series.iter().fold(None, |acc, (t, q)| match acc {
+        Some((_, min)) if min <= q => acc,
+        _ => Some((t, q)),
+    })
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod tests {
+    use super::*;
+    use crate::series::tests::{daily, day};
+
+    macro_rules! aw {
+        ($e:expr) => {
+            tokio_test::block_on($e)
+        };
+    }
+
+    #[test]
+    fn test_PriceDifference_calculate() {
+        let signal = PriceDifference {};
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        assert_eq!(aw!(signal.calculate(&daily(&[1.0]))), Some((0.0, 0.0)));
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[1.0, 0.0]))),
+            Some((-1.0, -1.0))
+        );
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0]))),
+            Some((8.0, 4.0))
+        );
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]))),
+            Some((1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn test_MinPrice_calculate() {
+        let signal = MinPrice {};
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        assert_eq!(aw!(signal.calculate(&daily(&[1.0]))), Some((day(0), 1.0)));
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[1.0, 0.0]))),
+            Some((day(1), 0.0))
+        );
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0]))),
+            Some((day(4), 1.0))
+        );
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]))),
+            Some((day(0), 0.0))
+        );
+    }
+
+    #[test]
+    fn test_MaxPrice_calculate() {
+        let signal = MaxPrice {};
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        assert_eq!(aw!(signal.calculate(&daily(&[1.0]))), Some((day(0), 1.0)));
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[1.0, 0.0]))),
+            Some((day(0), 1.0))
+        );
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[2.0, 3.0, 5.0, 6.0, 1.0, 2.0, 10.0]))),
+            Some((day(6), 10.0))
+        );
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[0.0, 3.0, 5.0, 6.0, 1.0, 2.0, 1.0]))),
+            Some((day(3), 6.0))
+        );
+    }
+
+    #[test]
+    fn test_WindowedSMA_calculate() {
+        let series = daily(&[2.0, 4.5, 5.3, 6.5, 4.7]);
+
+        let signal = WindowedSMA { window_size: 3 };
+        let sma = aw!(signal.calculate(&series)).unwrap();
+        assert_eq!(sma.values(), &[3.9333333333333336, 5.433333333333334, 5.5]);
+        assert_eq!(sma.timestamps(), &[day(2), day(3), day(4)]);
+
+        let signal = WindowedSMA { window_size: 5 };
+        assert_eq!(
+            aw!(signal.calculate(&series)),
+            Some(TimeSeries::new(vec![day(4)], vec![4.6]))
+        );
+
+        let signal = WindowedSMA { window_size: 10 };
+        assert_eq!(aw!(signal.calculate(&series)), Some(TimeSeries::default()));
+    }
+
+    #[test]
+    fn test_ExponentialMA_calculate() {
+        let series = daily(&[2.0, 4.5, 5.3, 6.5, 4.7]);
+
+        let signal = ExponentialMA::with_span(3);
+        assert_eq!(signal.alpha, 0.5);
+        let ema = aw!(signal.calculate(&series)).unwrap();
+        assert_eq!(
+            ema.values(),
+            &[3.9333333333333336, 5.216666666666667, 4.958333333333334]
+        );
+        assert_eq!(ema.timestamps(), &[day(2), day(3), day(4)]);
+
+        let signal = ExponentialMA::with_alpha(0.5);
+        assert_eq!(signal.span, 3);
+        assert_eq!(aw!(signal.calculate(&series)), Some(ema));
+
+        let signal = ExponentialMA::with_span(5);
+        assert_eq!(
+            aw!(signal.calculate(&series)),
+            Some(TimeSeries::new(vec![day(4)], vec![4.6]))
+        );
+
+        let signal = ExponentialMA::with_alpha(1.0);
+        assert_eq!(aw!(signal.calculate(&series)), Some(series.clone()));
+
+        let signal = ExponentialMA::with_span(10);
+        assert_eq!(aw!(signal.calculate(&series)), Some(TimeSeries::default()));
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        assert_eq!(aw!(ExponentialMA::with_alpha(0.0).calculate(&series)), None);
+    }
+
+    #[test]
+    fn test_Macd_calculate() {
+        // an EMA lags a linear trend by (span - 1) / 2
+        let series = daily(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let signal = Macd {
+            fast: 2,
+            slow: 4,
+            signal: 2,
+        };
+        let macd = aw!(signal.calculate(&series)).unwrap();
+        assert_eq!(macd.macd.values(), &[1.0, 1.0, 1.0]);
+        assert_eq!(macd.macd.timestamps(), &[day(3), day(4), day(5)]);
+        assert_eq!(macd.signal.values(), &[1.0, 1.0]);
+        assert_eq!(macd.histogram.values(), &[0.0, 0.0]);
+        assert_eq!(macd.histogram.timestamps(), &[day(4), day(5)]);
+
+        let short = aw!(signal.calculate(&daily(&[1.0, 2.0, 3.0, 4.0]))).unwrap();
+        assert_eq!(short.macd.values(), &[1.0]);
+        assert!(short.signal.is_empty() && short.histogram.is_empty());
+        assert_eq!(
+            aw!(signal.calculate(&daily(&[1.0, 2.0]))),
+            Some(MacdSeries::default())
+        );
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        let signal = Macd {
+            fast: 4,
+            slow: 2,
+            signal: 2,
+        };
+        assert_eq!(aw!(signal.calculate(&series)), None);
+    }
+
+    #[test]
+    fn test_MacdSeries_crossovers() {
+        let histogram = daily(&[-1.0, 0.5, 0.0, 0.2, -0.3, 0.0, -0.1, 0.4]);
+        let macd = MacdSeries {
+            histogram,
+            ..Default::default()
+        };
+        assert_eq!(
+            macd.crossovers(),
+            vec![
+                (day(1), Crossover::Bullish),
+                (day(4), Crossover::Bearish),
+                (day(7), Crossover::Bullish)
+            ]
+        );
+        assert_eq!(MacdSeries::default().crossovers(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_macd() {
+        assert_eq!("12,26,9".parse(), Ok(Macd::default()));
+        assert_eq!(Macd::default().to_string(), "12,26,9");
+        assert!("26,12,9".parse::<Macd>().is_err());
+        assert!("12,26".parse::<Macd>().is_err());
+        assert!("12,26,x".parse::<Macd>().is_err());
+    }
+
+    #[test]
+    fn test_BollingerBands_calculate() {
+        let series = daily(&[2.0, 4.0, 6.0, 6.0, 6.0]);
+
+        let signal = BollingerBands { window: 2, k: 2.0 };
+        let bands = aw!(signal.calculate(&series)).unwrap();
+        assert_eq!(bands.middle.values(), &[3.0, 5.0, 6.0, 6.0]);
+        assert_eq!(bands.upper.values(), &[5.0, 7.0, 6.0, 6.0]);
+        assert_eq!(bands.lower.values(), &[1.0, 3.0, 6.0, 6.0]);
+        assert_eq!(bands.percent_b.values(), &[0.75, 0.75, 0.5, 0.5]);
+        assert_eq!(
+            bands.percent_b.timestamps(),
+            &[day(1), day(2), day(3), day(4)]
+        );
+
+        let signal = BollingerBands { window: 4, k: 1.0 };
+        let bands = aw!(signal.calculate(&series)).unwrap();
+        // the standard deviation of 2, 4, 6, 6 is sqrt(2.75)
+        assert_eq!(bands.middle.values(), &[4.5, 5.5]);
+        assert_eq!(
+            bands.upper.values(),
+            &[4.5 + 2.75f64.sqrt(), 5.5 + 0.75f64.sqrt()]
+        );
+
+        let signal = BollingerBands { window: 10, k: 2.0 };
+        assert_eq!(
+            aw!(signal.calculate(&series)),
+            Some(BollingerSeries::default())
+        );
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        assert_eq!(
+            aw!(BollingerBands { window: 1, k: 2.0 }.calculate(&series)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_bollinger_bands() {
+        assert_eq!("20,2".parse(), Ok(BollingerBands::default()));
+        assert_eq!("10, 1.5".parse(), Ok(BollingerBands { window: 10, k: 1.5 }));
+        assert!("1,2".parse::<BollingerBands>().is_err());
+        assert!("20".parse::<BollingerBands>().is_err());
+        assert!("20,2,1".parse::<BollingerBands>().is_err());
+    }
+
+    #[test]
+    fn test_RollingVolatility_calculate() {
+        // log returns of ln(2), 0, ln(2), 0
+        let series = daily(&[1.0, 2.0, 2.0, 4.0, 4.0]);
+
+        let signal = RollingVolatility {
+            window: 2,
+            periods_per_year: 4.0,
+        };
+        let volatility = aw!(signal.calculate(&series)).unwrap();
+        let expected = 2.0f64.ln() / 2.0f64.sqrt() * 2.0;
+        assert_eq!(volatility.values(), &[expected, expected, expected]);
+        assert_eq!(volatility.timestamps(), &[day(2), day(3), day(4)]);
+
+        let signal = RollingVolatility {
+            window: 4,
+            periods_per_year: 1.0,
+        };
+        // the variance of ln(2) * (1, 0, 1, 0) is ln(2)^2 / 3
+        let volatility = aw!(signal.calculate(&series)).unwrap();
+        assert_eq!(volatility.timestamps(), &[day(4)]);
+        assert!((volatility.values()[0] - 2.0f64.ln() / 3.0f64.sqrt()).abs() < 1e-12);
+
+        let flat = daily(&[3.0; 4]);
+        assert_eq!(
+            aw!(signal.calculate(&flat)).unwrap().values(),
+            &[] as &[f64]
+        );
+        let signal = RollingVolatility {
+            window: 2,
+            periods_per_year: 1.0,
+        };
+        assert_eq!(aw!(signal.calculate(&flat)).unwrap().values(), &[0.0, 0.0]);
+        assert_eq!(aw!(signal.calculate(&daily(&[1.0, 0.0, 1.0]))), None);
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+    }
+
+    #[test]
+    fn test_MaxDrawdown_calculate() {
+        let signal = MaxDrawdown {};
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+
+        let drawdown =
+            aw!(signal.calculate(&daily(&[4.0, 5.0, 4.0, 5.0, 2.5, 4.0, 6.0, 5.0]))).unwrap();
+        assert_eq!(drawdown.max_drawdown, 0.5);
+        assert_eq!(
+            (drawdown.peak, drawdown.trough, drawdown.recovery),
+            (day(3), day(4), Some(day(6)))
+        );
+        assert_eq!(drawdown.longest_underwater, chrono::Duration::days(3));
+
+        // the largest loss doesn't recover, an earlier one takes longest
+        let drawdown =
+            aw!(signal.calculate(&daily(&[10.0, 9.0, 9.5, 9.8, 10.0, 11.0, 5.0, 6.0]))).unwrap();
+        assert!((drawdown.max_drawdown - 6.0 / 11.0).abs() < 1e-12);
+        assert_eq!(
+            (drawdown.peak, drawdown.trough, drawdown.recovery),
+            (day(5), day(6), None)
+        );
+        assert_eq!(drawdown.longest_underwater, chrono::Duration::days(4));
+
+        // on plateaus the first equal price is the peak
+        let drawdown = aw!(signal.calculate(&daily(&[10.0, 10.0, 5.0, 10.0, 10.0, 4.0]))).unwrap();
+        assert!((drawdown.max_drawdown - 0.6).abs() < 1e-12);
+        assert_eq!(
+            (drawdown.peak, drawdown.trough, drawdown.recovery),
+            (day(3), day(5), None)
+        );
+        assert_eq!(drawdown.longest_underwater, chrono::Duration::days(3));
+        let drawdown = aw!(signal.calculate(&daily(&[10.0, 10.0, 5.0]))).unwrap();
+        assert_eq!((drawdown.peak, drawdown.trough), (day(0), day(2)));
+        assert_eq!(drawdown.longest_underwater, chrono::Duration::days(2));
+
+        let drawdown = aw!(signal.calculate(&daily(&[1.0, 2.0, 3.0]))).unwrap();
+        assert_eq!(
+            drawdown,
+            Drawdown {
+                max_drawdown: 0.0,
+                peak: day(0),
+                trough: day(0),
+                recovery: None,
+                longest_underwater: chrono::Duration::zero(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_PerformanceStats_calculate() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+        // returns of 10%, -10%, 20%
+        let series = daily(&[100.0, 110.0, 99.0, 118.8]);
+        let signal = PerformanceStats {
+            risk_free_rate: 0.0,
+            periods_per_year: 3.0,
+        };
+        let stats = aw!(signal.calculate(&series)).unwrap();
+        assert!(close(stats.cagr, 0.188));
+        // mean 0.0666.., sample standard deviation 0.152752..
+        assert!(close(
+            stats.sharpe.unwrap(),
+            0.2 / 3.0 / (7.0f64 / 300.0).sqrt() * 3.0f64.sqrt()
+        ));
+        // only the loss of 10% counts: sqrt(0.01 / 3)
+        assert!(close(
+            stats.sortino.unwrap(),
+            0.2 / 3.0 / (0.01f64 / 3.0).sqrt() * 3.0f64.sqrt()
+        ));
+        assert!(close(stats.calmar.unwrap(), 1.88));
+        // drawdowns 0, 0, -10%, 0
+        assert!(close(stats.ulcer_index, 0.05));
+
+        // a risk-free return of 30% per year is 10% per period
+        let signal = PerformanceStats {
+            risk_free_rate: 0.3,
+            periods_per_year: 3.0,
+        };
+        let stats = aw!(signal.calculate(&series)).unwrap();
+        assert!(close(
+            stats.sharpe.unwrap(),
+            -0.1 / 3.0 / (7.0f64 / 300.0).sqrt() * 3.0f64.sqrt()
+        ));
+
+        let stats = aw!(PerformanceStats::default().calculate(&daily(&[2.0, 2.0, 2.0]))).unwrap();
+        assert_eq!(
+            stats,
+            Performance {
+                cagr: 0.0,
+                sharpe: None,
+                sortino: None,
+                calmar: None,
+                ulcer_index: 0.0,
+            }
+        );
+        assert_eq!(aw!(signal.calculate(&daily(&[2.0]))), None);
+        assert_eq!(aw!(signal.calculate(&daily(&[2.0, 0.0]))), None);
+    }
+
+    #[test]
+    fn test_BenchmarkStats_calculate() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+        // returns of 10%, -10%, 20% and twice those
+        let benchmark = daily(&[100.0, 110.0, 99.0, 118.8]);
+        let series = daily(&[50.0, 60.0, 48.0, 67.2, 1.0]);
+        let signal = BenchmarkStats {
+            benchmark: benchmark.clone(),
+            risk_free_rate: 0.0,
+            periods_per_year: 3.0,
+        };
+        let comparison = aw!(signal.calculate(&series)).unwrap();
+        assert!(close(comparison.beta.unwrap(), 2.0));
+        assert!(close(comparison.correlation.unwrap(), 1.0));
+        assert!(close(comparison.alpha.unwrap(), 0.0));
+        // 34.4% vs 18.8%
+        assert!(close(comparison.excess_return, 0.156));
+
+        // the risk-free return reduces the excess return of the beta
+        let signal = BenchmarkStats {
+            risk_free_rate: 0.3,
+            ..signal
+        };
+        let comparison = aw!(signal.calculate(&series)).unwrap();
+        assert!(close(comparison.alpha.unwrap(), 0.3));
+
+        let inverse = daily(&[100.0, 90.0, 99.0, 79.2]);
+        let comparison = aw!(signal.calculate(&inverse)).unwrap();
+        assert!(close(comparison.beta.unwrap(), -1.0));
+        assert!(close(comparison.correlation.unwrap(), -1.0));
+
+        let signal = BenchmarkStats {
+            benchmark: daily(&[2.0, 2.0, 2.0]),
+            ..signal
+        };
+        let comparison = aw!(signal.calculate(&series)).unwrap();
+        assert_eq!((comparison.beta, comparison.alpha), (None, None));
+        assert_eq!(comparison.correlation, None);
+        assert_eq!(aw!(signal.calculate(&daily(&[1.0]))), None);
+        assert_eq!(aw!(signal.calculate(&TimeSeries::default())), None);
+    }
+
+    #[test]
+    fn test_Rsi_calculate() {
+        let series = daily(&[2.0, 4.0, 3.0, 6.0, 5.0]);
+
+        let signal = Rsi { period: 2 };
+        let rsi = aw!(signal.calculate(&series)).unwrap();
+        // gains 2, 0, 3, 0 and losses 0, 1, 0, 1
+        assert_eq!(
+            rsi.values(),
+            &[66.66666666666666, 88.88888888888889, 61.53846153846154]
+        );
+        assert_eq!(rsi.timestamps(), &[day(2), day(3), day(4)]);
+
+        let signal = Rsi { period: 4 };
+        assert_eq!(
+            aw!(signal.calculate(&series)),
+            Some(TimeSeries::new(vec![day(4)], vec![71.42857142857143]))
+        );
+
+        let flat = daily(&[3.0; 5]);
+        assert_eq!(aw!(signal.calculate(&flat)).unwrap().values(), &[50.0]);
+        let gains = daily(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(
+            aw!(signal.calculate(&gains)).unwrap().values(),
+            &[100.0, 100.0]
+        );
+        let losses = daily(&[6.0, 5.0, 4.0, 3.0, 2.0]);
+        assert_eq!(aw!(signal.calculate(&losses)).unwrap().values(), &[0.0]);
+
+        let signal = Rsi { period: 5 };
+        assert_eq!(aw!(signal.calculate(&series)), None);
+        assert_eq!(aw!(signal.calculate(&daily(&[]))), None);
+        assert_eq!(aw!(Rsi { period: 0 }.calculate(&series)), None);
+    }
+}
@@ -0,0 +1,229 @@
+//!
+//! The yahoo! finance provider against recorded chart API responses.
+//!
+
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use stocks::mock::{MockResponse, MockYahoo};
+use stocks::{
+    fetch_actions, fetch_bars, fetch_closing_data, ActionKind, FetchErrorKind, Interval,
+    PriceBasis, RetryPolicy, YahooProvider,
+};
+
+macro_rules! aw {
+    ($e:expr) => {
+        tokio_test::block_on($e)
+    };
+}
+
+fn fixture(name: &str) -> MockResponse {
+    MockResponse::fixture(format!(
+        "{}/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+fn server(routes: Vec<(&str, Vec<MockResponse>)>) -> MockYahoo {
+    let routes: HashMap<_, _> = routes
+        .into_iter()
+        .map(|(symbol, responses)| (symbol.to_string(), responses))
+        .collect();
+    MockYahoo::start(routes).unwrap()
+}
+
+fn retry() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+        ..Default::default()
+    }
+}
+
+fn period() -> (DateTime<Utc>, DateTime<Utc>) {
+    (
+        Utc.ymd(2021, 1, 1).and_hms(0, 0, 0),
+        Utc.ymd(2021, 1, 9).and_hms(0, 0, 0),
+    )
+}
+
+#[test]
+fn test_fetch_closing_data() {
+    let server = server(vec![("AAPL", vec![fixture("AAPL")])]);
+    let provider = YahooProvider::with_url(&server.url());
+    let (from, to) = period();
+    let fetch = |basis| {
+        aw!(fetch_closing_data(
+            &provider,
+            &retry(),
+            "AAPL",
+            &from,
+            &to,
+            Interval::OneDay,
+            basis
+        ))
+        .unwrap()
+    };
+
+    let adjusted = fetch(PriceBasis::Adjusted);
+    assert_eq!(adjusted.values(), &[128.62, 130.21, 125.83, 130.12, 131.24]);
+    assert_eq!(
+        adjusted.first().unwrap().0,
+        Utc.ymd(2021, 1, 4).and_hms(14, 30, 0)
+    );
+    assert_eq!(
+        fetch(PriceBasis::Raw).values(),
+        &[129.41, 131.01, 126.6, 130.92, 132.05]
+    );
+    assert_eq!(
+        server.requests()[0],
+        format!(
+            "/AAPL?symbol=AAPL&period1={}&period2={}&interval=1d",
+            from.timestamp(),
+            to.timestamp()
+        )
+    );
+}
+
+#[test]
+fn test_fetch_bars_skips_bars_without_close() {
+    let server = server(vec![("MSFT", vec![fixture("MSFT")])]);
+    let provider = YahooProvider::with_url(&server.url());
+    let (from, to) = period();
+
+    let bars = aw!(fetch_bars(
+        &provider,
+        &retry(),
+        "MSFT",
+        &from,
+        &to,
+        Interval::OneDay
+    ))
+    .unwrap();
+    assert_eq!(bars.len(), 5);
+    assert_eq!(
+        (bars[0].open, bars[0].high, bars[0].low, bars[0].volume),
+        (222.53, 223.0, 214.81, 37130100)
+    );
+}
+
+#[test]
+fn test_fetch_intraday() {
+    let server = server(vec![("AAPL", vec![fixture("AAPL-5m")])]);
+    let provider = YahooProvider::with_url(&server.url());
+    let to = Utc::now();
+    let from = to - chrono::Duration::days(1);
+
+    let bars = aw!(fetch_bars(
+        &provider,
+        &retry(),
+        "AAPL",
+        &from,
+        &to,
+        Interval::FiveMinutes
+    ))
+    .unwrap();
+    // intraday charts have no adjusted close
+    assert_eq!(
+        bars.iter().map(|b| b.adjclose).collect::<Vec<_>>(),
+        vec![131.22, 131.5, 131.96, 132.1]
+    );
+    assert!(server.requests()[0].ends_with("&interval=5m"));
+
+    let old = Utc.ymd(2021, 1, 8).and_hms(0, 0, 0);
+    let error = aw!(fetch_bars(
+        &provider,
+        &retry(),
+        "AAPL",
+        &old,
+        &to,
+        Interval::FiveMinutes
+    ))
+    .unwrap_err();
+    assert!(matches!(error.kind, FetchErrorKind::InvalidRange(_)));
+    assert_eq!(
+        server.requests().len(),
+        1,
+        "invalid ranges aren't requested"
+    );
+}
+
+#[test]
+fn test_fetch_errors() {
+    let server = server(vec![
+        ("EMPTY", vec![fixture("EMPTY")]),
+        ("DOWN", vec![MockResponse::new(500, "")]),
+        ("DENIED", vec![MockResponse::new(403, "")]),
+        ("FLAKY", vec![MockResponse::new(503, ""), fixture("AAPL")]),
+        (
+            "LIMITED",
+            vec![
+                MockResponse::new(429, "").with_header("Retry-After", "0"),
+                fixture("AAPL"),
+            ],
+        ),
+        ("GARBAGE", vec![MockResponse::new(200, "<html>oops</html>")]),
+    ]);
+    let provider = YahooProvider::with_url(&server.url());
+    let (from, to) = period();
+    let fetch = |symbol| {
+        aw!(fetch_bars(
+            &provider,
+            &retry(),
+            symbol,
+            &from,
+            &to,
+            Interval::OneDay
+        ))
+    };
+    let requests = |symbol: &str| {
+        server
+            .requests()
+            .iter()
+            .filter(|r| r.starts_with(&format!("/{}?", symbol)))
+            .count()
+    };
+
+    assert!(matches!(
+        fetch("EMPTY").unwrap_err().kind,
+        FetchErrorKind::EmptyData
+    ));
+    assert!(matches!(
+        fetch("XYZ").unwrap_err().kind,
+        FetchErrorKind::UnknownSymbol
+    ));
+    assert_eq!(requests("XYZ"), 1);
+    assert!(matches!(
+        fetch("DOWN").unwrap_err().kind,
+        FetchErrorKind::Http(500)
+    ));
+    assert_eq!(requests("DOWN"), 3);
+    assert!(matches!(
+        fetch("DENIED").unwrap_err().kind,
+        FetchErrorKind::Rejected(403)
+    ));
+    assert_eq!(requests("DENIED"), 1, "client errors aren't retried");
+    assert_eq!(fetch("FLAKY").unwrap().len(), 5);
+    assert_eq!(requests("FLAKY"), 2);
+    assert_eq!(fetch("LIMITED").unwrap().len(), 5);
+    assert_eq!(requests("LIMITED"), 2);
+    assert!(matches!(
+        fetch("GARBAGE").unwrap_err().kind,
+        FetchErrorKind::MalformedResponse(_)
+    ));
+}
+
+#[test]
+fn test_fetch_actions() {
+    let server = server(vec![("AAPL", vec![fixture("AAPL")])]);
+    let provider = YahooProvider::with_url(&server.url());
+    let (from, to) = period();
+
+    let actions = aw!(fetch_actions(&provider, &retry(), "AAPL", &from, &to)).unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].timestamp, Utc.ymd(2021, 1, 6).and_hms(14, 30, 0));
+    assert_eq!(actions[0].kind, ActionKind::Dividend { amount: 0.205 });
+    assert!(server.requests()[0].ends_with("&events=div%7Csplit"));
+}
@@ -0,0 +1,124 @@
+//!
+//! Runs the binary against a mock of the yahoo! finance chart API serving recorded responses.
+//!
+
+use std::collections::HashMap;
+use std::process::{Command, Output};
+use stocks::mock::{MockResponse, MockYahoo};
+
+fn fixture(name: &str) -> MockResponse {
+    MockResponse::fixture(format!(
+        "{}/../stocks/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+fn server() -> MockYahoo {
+    let mut routes = HashMap::new();
+    routes.insert("AAPL".to_string(), vec![fixture("AAPL")]);
+    routes.insert("MSFT".to_string(), vec![fixture("MSFT")]);
+    routes.insert("EMPTY".to_string(), vec![fixture("EMPTY")]);
+    MockYahoo::start(routes).unwrap()
+}
+
+fn run(server: &MockYahoo, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sync-to-async"))
+        .args(["--yahoo-url", &server.url(), "--no-cache", "--retries", "1"])
+        .args(["--from", "2021-01-01T00:00:00Z"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_report() {
+    let server = server();
+    let output = run(&server, &["--symbols", "MSFT,AAPL"]);
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "period start,symbol,price,change %,min,max,30d avg\n\
+         2021-01-04T14:30:00+00:00,MSFT,$216.13,0.89%,$208.88,$216.13,$0.00\n\
+         2021-01-04T14:30:00+00:00,AAPL,$131.24,2.04%,$125.83,$131.24,$0.00\n"
+    );
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_report_raw_sorted() {
+    let server = server();
+    let output = run(
+        &server,
+        &["--symbols", "MSFT,AAPL", "--sorted", "--price-basis", "raw"],
+    );
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "period start,symbol,price,change %,min,max,30d avg\n\
+         2021-01-04T14:30:00+00:00,AAPL,$132.05,2.04%,$126.60,$132.05,$0.00\n\
+         2021-01-04T14:30:00+00:00,MSFT,$219.62,0.89%,$212.25,$219.62,$0.00\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_report_with_failures() {
+    let server = server();
+    let output = run(&server, &["--symbols", "EMPTY,AAPL,XYZ"]);
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "period start,symbol,price,change %,min,max,30d avg\n\
+         2021-01-04T14:30:00+00:00,AAPL,$131.24,2.04%,$125.83,$131.24,$0.00\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "2 of 3 symbols failed\n  EMPTY: no data\n  XYZ: unknown symbol\n"
+    );
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = run(&server, &["--symbols", "XYZ"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "period start,symbol,price,change %,min,max,30d avg\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_list_actions() {
+    let server = server();
+    let output = run(&server, &["--symbols", "AAPL", "--list-actions"]);
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "symbol,date,action,value\n\
+         AAPL,2021-01-06T14:30:00+00:00,dividend,0.205\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_record_replay() {
+    let server = server();
+    let cassette = std::env::temp_dir().join(format!("stocks-e2e-{}.cassette", std::process::id()));
+    let cassette = cassette.to_str().unwrap();
+    let symbols = ["--symbols", "MSFT,EMPTY,AAPL,XYZ"];
+
+    let recorded = run(&server, &[&symbols[..], &["--record", cassette]].concat());
+    let requests = server.requests().len();
+    let replayed = run(&server, &[&symbols[..], &["--replay", cassette]].concat());
+
+    assert_eq!(server.requests().len(), requests, "replays don't fetch");
+    assert_eq!(recorded.stdout, replayed.stdout);
+    assert_eq!(recorded.stderr, replayed.stderr);
+    assert_eq!(replayed.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(replayed.stdout).unwrap().lines().count(),
+        3
+    );
+    std::fs::remove_file(cassette).unwrap();
+}